@@ -0,0 +1,2 @@
+alias ll='echo hi'
+ll there
@@ -15,6 +15,9 @@ use std::ffi::CString;
 pub fn source_profile(runtime: &mut Runtime) {
     if let Some(mut path) = dirs::home_dir() {
         path.push(".oursh_profile");
+        if !path.exists() {
+            return;
+        }
         let argv = vec![
             CString::new("source".to_string()).unwrap(),
             CString::new(path.to_str().unwrap()).expect("valid path string"),
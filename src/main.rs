@@ -1,5 +1,3 @@
-#![feature(exclusive_range_pattern)]
-
 extern crate dirs;
 extern crate docopt;
 extern crate nix;
@@ -7,20 +5,20 @@ extern crate oursh;
 extern crate termion;
 
 use docopt::{Docopt, Value};
-use nix::sys::wait::WaitStatus;
+use nix::errno::Errno;
 use oursh::{
     invocation::source_profile,
-    process::{Jobs, IO},
-    program::{parse_and_run, Error, Result, Runtime},
+    process::{session, signal, ExitStatus, Jobs, IO},
+    program::{parse_and_run_stream, run_exit_trap, Aliases, Options, Result, Runtime, Traps},
     repl, VERSION,
 };
 use std::{
-    cell::RefCell,
     env,
     fs::File,
-    io::{self, Read},
+    io::{self, BufReader},
+    mem::ManuallyDrop,
+    os::unix::io::FromRawFd,
     process::{ExitCode, Termination},
-    rc::Rc,
 };
 use termion::is_tty;
 
@@ -75,19 +73,42 @@ fn main() -> MainResult {
     // TODO: From sh docs:
     //     "with an extension for support of a
     //      leading  <plus-sign> ('+') as noted below."
-    let mut args = Docopt::new(USAGE)
-        .and_then(|d| {
-            d.version(Some(VERSION.into()))
-                .argv(env::args().into_iter())
-                .parse()
-        })
+    let args = Docopt::new(USAGE)
+        .and_then(|d| d.version(Some(VERSION.into())).argv(env::args()).parse())
         .unwrap_or_else(|e| e.exit());
 
+    // The shell is interactive when it's reading commands from a terminal.
+    let operand = |name| matches!(args.find(name), Some(Value::Plain(Some(_))));
+    let interactive = args.get_bool("-i")
+        || (!operand("<command_string>") && !operand("<command_file>") && is_tty(&io::stdin()));
+    // Job control needs a terminal, and must be set up before the shell
+    // ignores the signals which would stop it.
+    if interactive && is_tty(&io::stdin()) {
+        session::init().ok();
+    }
+    signal::init(interactive);
+
     // Elementary job management.
-    let mut jobs: Jobs = Rc::new(RefCell::new(vec![]));
+    let mut jobs = Jobs::new();
 
     // Default inputs and outputs.
-    let mut io = IO::default();
+    let io = IO::default();
+
+    // The status of the last command, `$?`.
+    let mut status = ExitStatus::default();
+
+    // Aliases, defined with the `alias` builtin.
+    let mut aliases = Aliases::new();
+
+    // Traps, set with the `trap` builtin.
+    let mut traps = Traps::new();
+
+    // Options, set with the `set` builtin.
+    let mut options = Options {
+        interactive,
+        posix: args.get_bool("--posix"),
+        ..Options::default()
+    };
 
     #[cfg(feature = "history")]
     let mut history = History::load();
@@ -95,6 +116,12 @@ fn main() -> MainResult {
         io,
         jobs: &mut jobs,
         args: &args,
+        status: &mut status,
+        aliases: &mut aliases,
+        traps: &mut traps,
+        options: &mut options,
+        // Failed commands are reported as they fail.
+        report: |e| eprintln!("oursh: {}", e),
         background: false,
         #[cfg(feature = "history")]
         history: &mut history,
@@ -109,18 +136,22 @@ fn main() -> MainResult {
         source_profile(&mut runtime);
     }
 
-    if let Some(Value::Plain(Some(ref c))) = args.find("<command_string>") {
-        MainResult(parse_and_run(c, &mut runtime))
+    let result = if let Some(Value::Plain(Some(ref c))) = args.find("<command_string>") {
+        // Like a script, run each complete program as it's read, so aliases
+        // defined on one line apply to the next.
+        parse_and_run_stream("-c", c.as_bytes(), &mut runtime)
     } else if let Some(Value::Plain(Some(ref filename))) = args.find("<command_file>") {
-        let mut file =
-            File::open(filename).unwrap_or_else(|_| panic!("error opening file: {}", filename));
-
-        // Fill a string buffer from the file.
-        let mut text = String::new();
-        file.read_to_string(&mut text).expect("error reading file");
-
-        // Run the program.
-        MainResult(parse_and_run(&text, &mut runtime))
+        let file = match File::open(filename) {
+            Ok(file) => file,
+            Err(e) => {
+                let errno = Errno::from_i32(e.raw_os_error().unwrap_or_default());
+                eprintln!("oursh: {}: {}", filename, errno.desc());
+                return MainResult(Ok(ExitStatus::Exited(127)));
+            }
+        };
+
+        // Run each program in the file as it's read.
+        parse_and_run_stream(filename, BufReader::new(file), &mut runtime)
     } else {
         // Standard input file descriptor (0), used for user input from the
         // user of the shell.
@@ -137,33 +168,29 @@ fn main() -> MainResult {
             // to the user of the shell.
             let stdout = io::stdout();
 
-            // Trap SIGINT.
-            ctrlc::set_handler(move || println!()).unwrap();
-
-            let result = repl::start(stdin, stdout, &mut io, &mut jobs, &mut args);
-            MainResult(result)
+            // The REPL runs the `EXIT` trap itself.
+            return MainResult(repl::start(stdin, stdout, &mut runtime));
         } else {
-            // Fill a string buffer from STDIN.
-            let mut text = String::new();
-            stdin.lock().read_to_string(&mut text).unwrap();
-
-            // Run the program.
-            MainResult(parse_and_run(&text, &mut runtime))
+            // Run each program as soon as it's read. Reading STDIN directly,
+            // a byte at a time, leaves the rest of it for the commands we
+            // run, like `sh` does.
+            let file = ManuallyDrop::new(unsafe { File::from_raw_fd(0) });
+            let reader = BufReader::with_capacity(1, &*file);
+            parse_and_run_stream("stdin", reader, &mut runtime)
         }
-    }
+    };
+
+    run_exit_trap(&mut runtime);
+    MainResult(result)
 }
 
 #[derive(Debug)]
-struct MainResult(Result<WaitStatus>);
+struct MainResult(Result<ExitStatus>);
 impl Termination for MainResult {
     fn report(self) -> ExitCode {
         match self.0 {
-            Ok(WaitStatus::Exited(_pid, code)) => ExitCode::from(code as u8),
-            Ok(WaitStatus::Signaled(_pid, _signal, _coredump)) => ExitCode::from(128),
-            Ok(_) => ExitCode::from(0), // TODO: Is this even remotely correct?
-            Err(Error::Read) => ExitCode::from(1),
-            Err(Error::Parse) => ExitCode::from(2),
-            Err(Error::Runtime) => ExitCode::from(127),
+            Ok(status) => ExitCode::from(status.code() as u8),
+            Err(e) => ExitCode::from(e.status().code() as u8),
         }
     }
 }
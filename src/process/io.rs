@@ -1,31 +1,127 @@
-use nix::unistd::{close, dup2};
-use std::os::unix::io::RawFd;
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg},
+    unistd::{close, dup2},
+};
+use std::{collections::BTreeMap, os::unix::io::RawFd};
+
+/// The lowest descriptor the shell uses for files it opens for itself.
+///
+/// Keeping internal descriptors above the range users typically name (POSIX
+/// only requires 0-9) means `3>log` never clobbers one of ours.
+pub const INTERNAL_FD_MIN: RawFd = 10;
 
 /// File descriptors for use in processes and threads
-#[derive(Debug, Copy, Clone)]
-pub struct IO(pub [RawFd; 3]);
+///
+/// Maps each descriptor number as seen by a command (the `n` in `n>file`) to
+/// the shell's descriptor backing it, or `None` when it should be closed.
+/// Descriptors without an entry are inherited from the shell unchanged.
+///
+/// ```
+/// use oursh::process::IO;
+///
+/// let mut io = IO::default();
+/// io.set(3, 1);
+/// assert_eq!(Some(1), io.get(3));
+/// assert_eq!(Some(2), io.get(2));
+/// io.close(2);
+/// assert_eq!(None, io.get(2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IO(BTreeMap<RawFd, Option<RawFd>>);
 
 impl IO {
+    /// The shell's descriptor backing `n`, if it is open.
+    pub fn get(&self, n: RawFd) -> Option<RawFd> {
+        self.0.get(&n).copied().unwrap_or(Some(n))
+    }
+
+    /// Make `n` refer to the shell's descriptor `fd`.
+    ///
+    /// Setting `n` to itself is how an internal (close-on-exec) descriptor
+    /// is passed along to a command as is.
+    pub fn set(&mut self, n: RawFd, fd: RawFd) {
+        self.0.insert(n, Some(fd));
+    }
+
+    /// Mark `n` as closed.
+    pub fn close(&mut self, n: RawFd) {
+        self.0.insert(n, None);
+    }
+
+    /// Install this mapping onto the real descriptors of the current
+    /// process, this is meant to be called in a child after forking.
+    ///
+    /// Every source is first copied out of the way, so mappings like
+    /// `3>&1 1>&2` can't overwrite each other part way through.
     pub fn dup(&self) -> Result<(), nix::Error> {
-        if self.0[0] != 0 {
-            dup2(self.0[0], 0)?;
-            close(self.0[0])?;
-        }
-        if self.0[1] != 1 {
-            dup2(self.0[1], 1)?;
-            close(self.0[1])?;
+        let mut moved = vec![];
+        for (&n, &fd) in self.0.iter() {
+            let fd = match fd {
+                Some(fd) => Some(fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(INTERNAL_FD_MIN))?),
+                None => None,
+            };
+            moved.push((n, fd));
         }
-        if self.0[2] != 2 {
-            dup2(self.0[2], 2)?;
-            close(self.0[2])?;
+        for (n, fd) in moved {
+            match fd {
+                Some(fd) => {
+                    dup2(fd, n)?;
+                    close(fd)?;
+                }
+                None => {
+                    // Closing an already closed descriptor is fine.
+                    let _ = close(n);
+                }
+            }
         }
         Ok(())
     }
+
+    /// Copy the descriptors this mapping would replace out of the way,
+    /// giving the mapping which puts them back with [`IO::restore`].
+    pub fn save(&self) -> Result<IO, nix::Error> {
+        let mut saved = IO::default();
+        for &n in self.0.keys() {
+            match fcntl(n, FcntlArg::F_DUPFD_CLOEXEC(INTERNAL_FD_MIN)) {
+                Ok(fd) => saved.set(n, fd),
+                Err(Errno::EBADF) => saved.close(n),
+                Err(e) => {
+                    saved.discard();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Put back the descriptors saved by [`IO::save`].
+    pub fn restore(self) -> Result<(), nix::Error> {
+        let result = self.dup();
+        self.discard();
+        result
+    }
+
+    // Close the copies a saved mapping holds.
+    fn discard(&self) {
+        for fd in self.0.values().flatten() {
+            let _ = close(*fd);
+        }
+    }
 }
 
-impl Default for IO {
-    fn default() -> Self {
-        // [stdin, stdout, stderr]
-        IO([0, 1, 2])
+/// Take ownership of a descriptor the shell opened for itself.
+///
+/// The descriptor is moved to at least [`INTERNAL_FD_MIN`] and marked
+/// close-on-exec, so it is only ever seen by children through an [`IO`]
+/// mapping.
+pub fn internal(fd: RawFd) -> Result<RawFd, nix::Error> {
+    if fd >= INTERNAL_FD_MIN {
+        fcntl(fd, FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC))?;
+        Ok(fd)
+    } else {
+        let moved = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(INTERNAL_FD_MIN))?;
+        close(fd)?;
+        Ok(moved)
     }
 }
@@ -1,39 +1,587 @@
-use crate::process::{ProcessGroup, Wait};
-use nix::sys::wait::WaitStatus;
-use std::{cell::RefCell, rc::Rc};
+use crate::process::{
+    session,
+    usage::{self, Usage},
+    ExitStatus, Process, ProcessGroup, Wait,
+};
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::Signal,
+        wait::{WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+use std::{
+    convert::TryFrom,
+    fmt,
+    ops::{Index, IndexMut},
+    time::{Duration, Instant},
+};
 
-/// Shared job handling structure
+/// The shell's table of jobs.
 ///
-/// Maintains a collection of process groups.
-// TODO: Make into slightly better struct.
-pub type Jobs = Rc<RefCell<Vec<(String, ProcessGroup)>>>;
-
-/// Enumerate the given jobs, pruning exited, signaled or otherwise errored process groups
-pub fn retain_alive(jobs: &mut Jobs) {
-    jobs.borrow_mut().retain_mut(|job| {
-        let id = job.0.clone();
-        let body = job.1.leader().body();
-        match job.1.leader().status() {
-            Ok(WaitStatus::StillAlive) => true,
-            Ok(WaitStatus::Exited(pid, code)) => {
-                println!("[{}]+\tExit({})\t{}\t{}", id, code, pid, body);
-                false
-            }
-            Ok(WaitStatus::Signaled(pid, signal, _)) => {
-                println!("[{}]+\t{}\t{}\t{}", id, signal, pid, body);
-                false
-            }
-            Ok(_) => {
-                println!("unhandled");
-                true
-            }
-            Err(e) => {
-                if nix::errno::Errno::ECHILD != e {
-                    println!("err: {:?}", e);
+/// Each job is numbered when it's started, one more than the highest
+/// number in use, and keeps its number until it's done. The *current* job
+/// is the one most recently started or stopped, and the *previous* job the
+/// one before it, marked `+` and `-` respectively.
+///
+/// Jobs which stop or finish in the background are reported the next time
+/// the shell gets the chance, see [`notify`](Jobs::notify). Once a finished
+/// job is forgotten the statuses of its processes are still remembered, for
+/// `wait` to give.
+///
+/// With `set -o timing` the time each job took is recorded as it's
+/// forgotten, see [`timings`](Jobs::timings).
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    // The IDs of the jobs, from the least to the most recently started or
+    // stopped.
+    recent: Vec<usize>,
+    // The IDs of the jobs which have stopped or finished since they were
+    // last reported.
+    changed: Vec<usize>,
+    // The statuses of the processes of forgotten jobs, oldest first.
+    statuses: Vec<(Pid, ExitStatus)>,
+    // The last process of the most recent background job, `$!`.
+    background: Option<Pid>,
+    // Are finished jobs timed?
+    timing: bool,
+    // The times of finished jobs, oldest first.
+    timings: Vec<Timing>,
+    // Processes started on behalf of a command, like those feeding a
+    // process substitution, which aren't jobs of their own but still need
+    // to be reaped.
+    helpers: Vec<Process>,
+}
+
+// How many statuses of forgotten jobs' processes, and times of finished
+// jobs, are remembered.
+const REMEMBERED: usize = 1024;
+
+impl Jobs {
+    pub fn new() -> Self {
+        Jobs::default()
+    }
+
+    /// Note that this is a forked copy of the shell. Its jobs are still
+    /// listed, but their processes are children of the shell it was copied
+    /// from, which it can't wait on, see [`Job::inherited`].
+    pub fn forked(&mut self) {
+        for job in &mut self.jobs {
+            job.inherited = true;
+        }
+        self.helpers.clear();
+    }
+
+    /// Add a job for the group just started, running `text`, which becomes
+    /// the current job. Returns the job's ID.
+    pub fn add(&mut self, text: String, group: ProcessGroup) -> usize {
+        let id = self.jobs.last().map_or(1, |job| job.id + 1);
+        let states = vec![State::Running; group.processes().len()];
+        self.jobs.push(Job {
+            id,
+            text,
+            group,
+            states,
+            hangup: true,
+            inherited: false,
+            started: Instant::now(),
+            usage: Usage::default(),
+            elapsed: None,
+        });
+        self.recent.push(id);
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    /// Forget the job, once it's done or no longer the shell's concern.
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        self.recent.retain(|recent| *recent != id);
+        self.changed.retain(|changed| *changed != id);
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        let job = self.jobs.remove(index);
+        if let (true, Some(real)) = (self.timing, job.elapsed) {
+            self.timings.push(Timing {
+                text: job.text.clone(),
+                real,
+                usage: job.usage,
+            });
+            let excess = self.timings.len().saturating_sub(REMEMBERED);
+            self.timings.drain(..excess);
+        }
+        Some(job)
+    }
+
+    /// Whether finished jobs are timed, which is `set -o timing`.
+    pub fn is_timing(&self) -> bool {
+        self.timing
+    }
+
+    /// Time the jobs which finish from now on, or stop timing them.
+    pub fn set_timing(&mut self, timing: bool) {
+        self.timing = timing;
+    }
+
+    /// The times of the finished jobs, oldest first, recorded while
+    /// [`is_timing`](Jobs::is_timing) is on.
+    pub fn timings(&self) -> &[Timing] {
+        &self.timings
+    }
+
+    /// Forget the finished job, remembering the statuses of its processes.
+    pub fn forget(&mut self, id: usize) {
+        if let Some(job) = self.remove(id) {
+            for (process, state) in job.processes() {
+                if let State::Done(status) = state {
+                    self.statuses.push((process.pid(), status));
                 }
+            }
+            let excess = self.statuses.len().saturating_sub(REMEMBERED);
+            self.statuses.drain(..excess);
+        }
+    }
+
+    /// Take the remembered status of a process of a forgotten job.
+    pub fn status(&mut self, pid: Pid) -> Option<ExitStatus> {
+        let index = self.statuses.iter().rposition(|(p, _)| *p == pid)?;
+        Some(self.statuses.remove(index).1)
+    }
+
+    /// Stop remembering the statuses of forgotten jobs.
+    pub fn clear_statuses(&mut self) {
+        self.statuses.clear();
+    }
+
+    /// The job containing the process.
+    pub fn containing(&self, pid: Pid) -> Option<usize> {
+        self.jobs
+            .iter()
+            .find(|job| job.group.processes().iter().any(|p| p.pid() == pid))
+            .map(Job::id)
+    }
+
+    /// Note that the job was started in the background, making its last
+    /// process `$!`.
+    pub fn backgrounded(&mut self, id: usize) {
+        self.background = self[id].group.processes().last().map(Process::pid);
+    }
+
+    /// The last process of the most recent background job, `$!`.
+    pub fn last_background(&self) -> Option<Pid> {
+        self.background
+    }
+
+    /// The jobs, in order of their IDs.
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
 
-                false
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Make the job the current job, the current job becoming the previous
+    /// job.
+    pub fn promote(&mut self, id: usize) {
+        self.recent.retain(|recent| *recent != id);
+        self.recent.push(id);
+    }
+
+    /// The ID of the current job, `%+`.
+    pub fn current(&self) -> Option<usize> {
+        self.recent.last().copied()
+    }
+
+    /// The ID of the previous job, `%-`.
+    pub fn previous(&self) -> Option<usize> {
+        self.recent.iter().rev().nth(1).copied()
+    }
+
+    /// The job's marker when it's listed, `+` for the current job, `-` for
+    /// the previous job and a space for any other.
+    pub fn marker(&self, id: usize) -> char {
+        if self.current() == Some(id) {
+            '+'
+        } else if self.previous() == Some(id) {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    /// The job as it's reported, with its ID, marker, state and command.
+    pub fn report(&self, id: usize) -> String {
+        let job = &self[id];
+        format!("[{}]{}\t{}\t{}", id, self.marker(id), job.state(), job.text)
+    }
+
+    /// Find the ID of the job a job spec refers to. The spec is written
+    /// without its leading `%`.
+    ///
+    /// - `n` is the job numbered `n`
+    /// - `+`, `%` or nothing at all is the current job
+    /// - `-` is the previous job
+    /// - `?string` is the job with a command containing `string`
+    /// - `string` is the job with a command starting with `string`
+    ///
+    /// ```
+    /// use oursh::process::{Jobs, Process, ProcessGroup};
+    /// use std::ffi::CString;
+    ///
+    /// let mut jobs = Jobs::new();
+    /// for command in ["sleep 5", "vim notes"] {
+    ///     let argv = command.split(' ').map(|a| CString::new(a).unwrap()).collect();
+    ///     jobs.add(command.into(), ProcessGroup::new(Process::new(argv)));
+    /// }
+    /// assert_eq!(Ok(2), jobs.find("+"));
+    /// assert_eq!(Ok(1), jobs.find("-"));
+    /// assert_eq!(Ok(1), jobs.find("1"));
+    /// assert_eq!(Ok(2), jobs.find("vi"));
+    /// assert_eq!(Ok(1), jobs.find("?5"));
+    /// assert_eq!(Err("ambiguous job spec"), jobs.find("?e"));
+    /// assert_eq!(Err("no such job"), jobs.find("3"));
+    ///
+    /// jobs.promote(1);
+    /// assert_eq!(Ok(1), jobs.find("%"));
+    /// assert_eq!(Ok(2), jobs.find("-"));
+    /// ```
+    pub fn find(&self, spec: &str) -> Result<usize, &'static str> {
+        let found: Vec<usize> = match spec {
+            "" | "+" | "%" => self.current().into_iter().collect(),
+            "-" => self.previous().into_iter().collect(),
+            spec => {
+                let matches = |job: &&Job| {
+                    if let Ok(id) = spec.parse::<usize>() {
+                        job.id == id
+                    } else if let Some(text) = spec.strip_prefix('?') {
+                        job.text.contains(text)
+                    } else {
+                        job.text.starts_with(spec)
+                    }
+                };
+                self.jobs.iter().filter(matches).map(Job::id).collect()
+            }
+        };
+        match found.as_slice() {
+            [] => Err("no such job"),
+            [id] => Ok(*id),
+            _ => Err("ambiguous job spec"),
+        }
+    }
+
+    /// Check up on every job without blocking, recording the jobs which
+    /// stopped or finished to be reported. Stopped jobs become the current
+    /// job.
+    pub fn poll(&mut self) {
+        let changed: Vec<usize> = self
+            .jobs
+            .iter_mut()
+            .filter_map(|job| (job.poll() && job.state() != State::Running).then_some(job.id))
+            .collect();
+        for id in changed {
+            if let State::Stopped(_) = self[id].state() {
+                self.promote(id);
             }
+            self.reported(id);
+            self.changed.push(id);
         }
-    });
+    }
+
+    /// Are there jobs which stopped or finished since they were last
+    /// reported?
+    pub fn unreported(&self) -> bool {
+        !self.changed.is_empty()
+    }
+
+    /// Mark the job as reported, so it's only reported again if it changes.
+    pub fn reported(&mut self, id: usize) {
+        self.changed.retain(|changed| *changed != id);
+    }
+
+    /// Keep a process started on behalf of a command, like one feeding a
+    /// process substitution, to be reaped once it's done without it being a
+    /// job of its own.
+    pub fn add_helper(&mut self, helper: Process) {
+        self.helpers.push(helper);
+    }
+
+    /// Reap any finished helper processes, quietly.
+    pub fn reap_helpers(&mut self) {
+        self.helpers
+            .retain(|helper| matches!(helper.status(), Ok(WaitStatus::StillAlive)));
+    }
+
+    /// Send every job `SIGHUP`, followed by `SIGCONT` so stopped jobs get
+    /// it too, as the shell hangs up. Jobs marked by `disown -h` are left
+    /// alone.
+    pub fn hangup(&self) {
+        for job in self.jobs.iter().filter(|job| job.hangup) {
+            job.group.signal(Some(Signal::SIGHUP)).ok();
+            job.group.signal(Some(Signal::SIGCONT)).ok();
+        }
+    }
+
+    /// Report the jobs which stopped or finished since they were last
+    /// reported, and forget the finished ones.
+    ///
+    /// Without job control, as in a script, finished jobs are forgotten
+    /// quietly.
+    pub fn notify(&mut self) {
+        for id in std::mem::take(&mut self.changed) {
+            if session::enabled() {
+                eprintln!("{}", self.report(id));
+            }
+        }
+        let done: Vec<usize> = self
+            .jobs
+            .iter()
+            .filter(|job| matches!(job.state(), State::Done(_)))
+            .map(Job::id)
+            .collect();
+        for id in done {
+            self.forget(id);
+        }
+    }
+}
+
+/// Panics if there's no job with the ID.
+impl Index<usize> for Jobs {
+    type Output = Job;
+
+    fn index(&self, id: usize) -> &Job {
+        self.get(id).expect("no such job")
+    }
+}
+
+impl IndexMut<usize> for Jobs {
+    fn index_mut(&mut self, id: usize) -> &mut Job {
+        self.get_mut(id).expect("no such job")
+    }
+}
+
+/// A process group the shell started, along with the state of each of its
+/// processes.
+#[derive(Debug)]
+pub struct Job {
+    id: usize,
+    text: String,
+    group: ProcessGroup,
+    states: Vec<State>,
+    // Is the job sent `SIGHUP` when the shell hangs up?
+    hangup: bool,
+    // Was the job started by the shell this is a forked copy of?
+    inherited: bool,
+    started: Instant,
+    // The time used by the job's processes which have finished.
+    usage: Usage,
+    // How long the job took, once it's done.
+    elapsed: Option<Duration>,
+}
+
+impl Job {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The command the job is running, the whole pipeline as it was
+    /// written.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn group(&self) -> &ProcessGroup {
+        &self.group
+    }
+
+    pub fn into_group(self) -> ProcessGroup {
+        self.group
+    }
+
+    /// Keep the job from being sent `SIGHUP` when the shell hangs up.
+    pub fn ignore_hangup(&mut self) {
+        self.hangup = false;
+    }
+
+    /// Each process of the job, with its state.
+    pub fn processes(&self) -> impl Iterator<Item = (&Process, State)> {
+        self.group
+            .processes()
+            .iter()
+            .zip(self.states.iter().copied())
+    }
+
+    /// The state of the job as a whole. It's stopped if any of its
+    /// processes are, and done once all of them are, with the status of
+    /// the last.
+    pub fn state(&self) -> State {
+        if let Some(stopped) = self.states.iter().find(|s| matches!(s, State::Stopped(_))) {
+            *stopped
+        } else if self.states.iter().all(|s| matches!(s, State::Done(_))) {
+            self.states[self.states.len() - 1]
+        } else {
+            State::Running
+        }
+    }
+
+    /// Mark the stopped processes as running again, once the job has been
+    /// sent `SIGCONT`.
+    pub fn continued(&mut self) {
+        for state in self.states.iter_mut() {
+            if let State::Stopped(_) = state {
+                *state = State::Running;
+            }
+        }
+    }
+
+    /// The time used by the job's processes, once they've finished.
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /// How long the job took, once it's done.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// Was the job started by another shell, which this one is a forked
+    /// copy of? Its processes can't be waited on, so its state is only
+    /// what it was when the shell was copied.
+    pub fn inherited(&self) -> bool {
+        self.inherited
+    }
+
+    /// Check up on the job's processes without blocking, returning whether
+    /// the state of the job changed.
+    pub fn poll(&mut self) -> bool {
+        let before = self.state();
+        let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+        for (process, state) in self.group.processes().iter().zip(self.states.iter_mut()) {
+            if let State::Done(_) = state {
+                continue;
+            }
+            if let Ok((status, usage)) = usage::wait(process.pid(), Some(flags)) {
+                *state = State::from_wait(status).unwrap_or(*state);
+                self.usage += usage;
+            }
+        }
+        self.finish();
+        self.state() != before
+    }
+
+    /// Wait for the job's running processes to finish, or for one of them
+    /// to stop. Returns the job's status, which is the status of its last
+    /// process, or how it was stopped.
+    pub fn wait(&mut self) -> nix::Result<ExitStatus> {
+        if self.inherited {
+            return Err(Errno::ECHILD);
+        }
+        for (process, state) in self.group.processes().iter().zip(self.states.iter_mut()) {
+            if *state != State::Running {
+                continue;
+            }
+            match usage::wait(process.pid(), Some(WaitPidFlag::WUNTRACED)) {
+                Ok((status, usage)) => {
+                    *state = State::from_wait(status).unwrap_or(*state);
+                    self.usage += usage;
+                }
+                Err(e) => return Err(e),
+            }
+            if let State::Stopped(_) = state {
+                break;
+            }
+        }
+        self.finish();
+        Ok(match self.state() {
+            State::Stopped(signal) => ExitStatus::Stopped(signal),
+            State::Done(status) => status,
+            State::Running => ExitStatus::SUCCESS,
+        })
+    }
+
+    // Note how long the job took, if it's just finished.
+    fn finish(&mut self) {
+        if let (None, State::Done(_)) = (self.elapsed, self.state()) {
+            self.elapsed = Some(self.started.elapsed());
+        }
+    }
+}
+
+/// How long a finished job took, and the time its processes used.
+#[derive(Debug, Clone)]
+pub struct Timing {
+    /// The command the job ran.
+    pub text: String,
+    pub real: Duration,
+    pub usage: Usage,
+}
+
+/// How a process of a job is doing, as of the last time the shell checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    /// Stopped by the signal, until it's continued.
+    Stopped(Signal),
+    /// Finished, with the given status.
+    Done(ExitStatus),
+}
+
+impl State {
+    // The state a process changed to, if it did.
+    fn from_wait(status: WaitStatus) -> Option<Self> {
+        match status {
+            WaitStatus::Continued(_) => Some(State::Running),
+            WaitStatus::Stopped(_, signal) => Some(State::Stopped(signal)),
+            status => ExitStatus::try_from(status).ok().map(State::Done),
+        }
+    }
+}
+
+/// States are written the way `jobs` lists them.
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            State::Running => write!(f, "Running"),
+            State::Stopped(Signal::SIGTSTP) => write!(f, "Stopped"),
+            State::Stopped(Signal::SIGTTIN) => write!(f, "Stopped (tty input)"),
+            State::Stopped(Signal::SIGTTOU) => write!(f, "Stopped (tty output)"),
+            State::Stopped(_) => write!(f, "Stopped (signal)"),
+            State::Done(ExitStatus::Exited(0)) => write!(f, "Done"),
+            State::Done(ExitStatus::Exited(code)) => write!(f, "Exit {}", code),
+            State::Done(ExitStatus::Signaled(signal, core)) => {
+                write!(f, "{}", describe(*signal))?;
+                if *core {
+                    write!(f, " (core dumped)")?;
+                }
+                Ok(())
+            }
+            State::Done(ExitStatus::Stopped(signal)) => State::Stopped(*signal).fmt(f),
+        }
+    }
+}
+
+// What happened to a process killed by the signal, in words.
+fn describe(signal: Signal) -> &'static str {
+    match signal {
+        Signal::SIGHUP => "Hangup",
+        Signal::SIGINT => "Interrupt",
+        Signal::SIGQUIT => "Quit",
+        Signal::SIGILL => "Illegal instruction",
+        Signal::SIGABRT => "Aborted",
+        Signal::SIGKILL => "Killed",
+        Signal::SIGSEGV => "Segmentation fault",
+        Signal::SIGPIPE => "Broken pipe",
+        Signal::SIGALRM => "Alarm clock",
+        Signal::SIGTERM => "Terminated",
+        Signal::SIGUSR1 => "User defined signal 1",
+        Signal::SIGUSR2 => "User defined signal 2",
+        signal => signal.as_str(),
+    }
 }
@@ -14,18 +14,34 @@
 
 use nix::{
     errno::Errno,
-    sys::wait::{waitpid, WaitPidFlag, WaitStatus},
-    unistd::{self, execvp, getpid, ForkResult, Pid},
+    sys::{
+        signal::{kill, killpg, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{self, access, execv, getpid, AccessFlags, ForkResult, Pid},
+};
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    env,
+    ffi::{CString, OsStr},
+    fs::File,
+    io::Read,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::exit,
 };
-use std::{borrow::Cow, ffi::CString, process::exit};
 
-mod io;
+pub mod io;
 pub use self::io::IO;
 pub mod jobs;
 pub use self::jobs::Jobs;
-mod session;
-mod signal;
+mod status;
+pub use self::status::ExitStatus;
+pub mod session;
+pub mod signal;
 mod thread;
+pub mod usage;
 
 /// A process to be executed by various means
 ///
@@ -63,39 +79,190 @@ impl Process {
         self.pid
     }
 
-    /// Run a shell job in the background.
-    pub fn fork(argv: Vec<CString>, io: IO) -> Result<Self, nix::Error> {
+    /// Run the command in a new process, as part of the job `pgid` or a new
+    /// job of its own.
+    ///
+    /// See [`fork_job`](Process::fork_job) for how jobs are handled.
+    pub fn fork(
+        argv: Vec<CString>,
+        io: &IO,
+        pgid: Option<Pid>,
+        foreground: bool,
+    ) -> Result<Self, nix::Error> {
+        let process = Process::new(argv.clone());
+        Process::fork_job(argv, pgid, foreground, || process.exec_with(io))
+    }
+
+    /// Run `f` in a forked copy of the shell as part of a job, the child
+    /// exits with the status `f` returns.
+    ///
+    /// With job control on the child joins the process group `pgid`, or
+    /// leads a new one, and a `foreground` job is handed the terminal.
+    pub fn fork_job<F>(
+        argv: Vec<CString>,
+        pgid: Option<Pid>,
+        foreground: bool,
+        f: F,
+    ) -> Result<Self, nix::Error>
+    where
+        F: FnOnce() -> i32,
+    {
         match unsafe { unistd::fork() } {
-            Ok(ForkResult::Parent { child }) => Ok(Process { argv, pid: child }),
+            Ok(ForkResult::Parent { child }) => {
+                if session::enabled() {
+                    let pgid = session::join(child, pgid);
+                    if foreground {
+                        session::foreground(pgid);
+                    }
+                }
+                Ok(Process { argv, pid: child })
+            }
             Ok(ForkResult::Child) => {
-                let process = Process {
-                    argv,
-                    pid: getpid(),
-                };
-                io.dup()?;
-                if let Err(e) = process.exec() {
-                    match e {
-                        Errno::ENOENT => {
-                            let name = process.argv[0].to_string_lossy();
-                            eprintln!("oursh: {}: command not found", name);
-                            exit(127);
-                        }
-                        _ => exit(128),
+                if session::enabled() {
+                    let pgid = session::join(getpid(), pgid);
+                    if foreground {
+                        session::foreground(pgid);
                     }
-                } else {
-                    unreachable!()
+                    session::disable();
                 }
+                signal::reset();
+                exit(f())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run `f` in a forked copy of the shell, the child exits with the
+    /// status `f` returns.
+    ///
+    /// Unlike [`fork`](Process::fork) nothing is exec'd, so this is how the
+    /// shell runs its own programs in the background, for example to feed a
+    /// process substitution. These helpers aren't jobs of their own.
+    pub fn fork_shell<F>(argv: Vec<CString>, f: F) -> Result<Self, nix::Error>
+    where
+        F: FnOnce() -> i32,
+    {
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Parent { child }) => Ok(Process { argv, pid: child }),
+            Ok(ForkResult::Child) => {
+                session::disable();
+                signal::reset();
+                exit(f())
             }
             Err(e) => Err(e),
         }
     }
 
-    fn exec(&self) -> Result<(), nix::Error> {
-        execvp(
-            &self.argv[0],
-            &self.argv.iter().map(|a| a.as_c_str()).collect::<Vec<_>>()[..],
-        )
-        .map(|_| ())
+    /// Replace the current process with this process's command, once the
+    /// descriptors are set up as `io` says.
+    ///
+    /// This is meant to be called in a child after forking, and only returns
+    /// on failure, with the status the child should exit with: 127 when the
+    /// command can't be found, and 126 when it can't be run.
+    pub fn exec_with(&self, io: &IO) -> i32 {
+        let name = self.argv[0].to_string_lossy();
+        // We're the child now, errors can't be returned to the shell.
+        if let Err(e) = io.dup() {
+            eprintln!("oursh: {}: {}", name, e.desc());
+            return 1;
+        }
+        match self.exec() {
+            Err(Errno::ENOENT) if !name.contains('/') => {
+                eprintln!("oursh: {}: command not found", name);
+                127
+            }
+            Err(e @ Errno::ENOENT) => {
+                eprintln!("oursh: {}: {}", name, e.desc());
+                127
+            }
+            Err(Errno::ENOEXEC) => {
+                eprintln!("oursh: {}: cannot execute binary file", name);
+                126
+            }
+            Err(e) => {
+                eprintln!("oursh: {}: {}", name, e.desc());
+                126
+            }
+            Ok(()) => unreachable!(),
+        }
+    }
+
+    /// Replace the current process image with this process's command.
+    ///
+    /// The command is looked for in `$PATH`, unless its name has a `/` in
+    /// it. A file the system can't run itself which isn't a binary, like a
+    /// script without a `#!` line, is run as a script by a new shell.
+    ///
+    /// This only ever returns on failure.
+    pub fn exec(&self) -> Result<(), nix::Error> {
+        let path = self.path()?;
+        let file = CString::new(path.as_os_str().as_bytes()).map_err(|_| Errno::EINVAL)?;
+        let args: Vec<_> = self.argv.iter().map(|a| a.as_c_str()).collect();
+        match execv(&file, &args) {
+            Err(Errno::ENOEXEC) if !binary(&path) => {
+                let shell = env::current_exe().map_err(|_| Errno::ENOEXEC)?;
+                let shell =
+                    CString::new(shell.as_os_str().as_bytes()).map_err(|_| Errno::EINVAL)?;
+                let noprofile = CString::new("--noprofile").unwrap();
+                let mut args = vec![shell.as_c_str(), &noprofile, &file];
+                args.extend(self.argv[1..].iter().map(|a| a.as_c_str()));
+                execv(&shell, &args).map(|_| ())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    /// Is there a file to run for this process's command, either in `$PATH`
+    /// or at its path?
+    pub fn found(&self) -> bool {
+        !matches!(self.path(), Err(Errno::ENOENT))
+    }
+
+    // The file to run, found in `$PATH` unless the name has a `/`. When none
+    // of the files found can be run, the first is still given, so running it
+    // fails the way it should.
+    fn path(&self) -> Result<PathBuf, nix::Error> {
+        let name = OsStr::from_bytes(self.argv[0].to_bytes());
+        if name.as_bytes().contains(&b'/') {
+            let path = PathBuf::from(name);
+            return if path.is_dir() {
+                Err(Errno::EISDIR)
+            } else {
+                Ok(path)
+            };
+        }
+        if name.is_empty() {
+            return Err(Errno::ENOENT);
+        }
+
+        let paths = env::var_os("PATH").unwrap_or_else(|| "/usr/bin:/bin".into());
+        let found: Vec<PathBuf> = env::split_paths(&paths)
+            // An empty entry is the current directory.
+            .map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    PathBuf::from(".").join(name)
+                } else {
+                    dir.join(name)
+                }
+            })
+            .filter(|path| path.exists() && !path.is_dir())
+            .collect();
+        found
+            .iter()
+            .find(|path| access(path.as_path(), AccessFlags::X_OK).is_ok())
+            .or_else(|| found.first())
+            .cloned()
+            .ok_or(Errno::ENOENT)
+    }
+}
+
+// Does the file look like a binary, rather than text? Like other shells,
+// only the start of the file is checked, for a NUL byte.
+fn binary(path: &Path) -> bool {
+    let mut start = [0; 80];
+    match File::open(path).and_then(|mut file| file.read(&mut start)) {
+        Ok(n) => start[..n].contains(&0),
+        Err(_) => true,
     }
 }
 
@@ -135,14 +302,88 @@ impl Wait for Process {
 /// using the system call getpgrp(), or, equivalently, getpgid(0). One finds the process group ID
 /// of process p using getpgid(p).
 #[derive(Debug)]
-pub struct ProcessGroup(pub Process); // TODO: Make a sorted vector of Process?
+pub struct ProcessGroup(Vec<Process>);
 
 impl ProcessGroup {
+    /// A new group, led by the given process.
+    pub fn new(leader: Process) -> Self {
+        ProcessGroup(vec![leader])
+    }
+
     pub fn leader(&self) -> &Process {
-        &self.0
+        &self.0[0]
     }
 
     pub fn leader_mut(&mut self) -> &mut Process {
-        &mut self.0
+        &mut self.0[0]
+    }
+
+    /// The group's ID, which is its leader's process ID.
+    pub fn pgid(&self) -> Pid {
+        self.leader().pid()
+    }
+
+    /// Add a process to the group, after the rest.
+    pub fn push(&mut self, process: Process) {
+        self.0.push(process)
+    }
+
+    /// The processes in the group, starting with the leader.
+    pub fn processes(&self) -> &[Process] {
+        &self.0
+    }
+
+    pub fn into_processes(self) -> Vec<Process> {
+        self.0
+    }
+
+    /// The commands of the group, as a pipeline.
+    pub fn body(&self) -> String {
+        self.0
+            .iter()
+            .map(Process::body)
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// Send the signal to every process in the group, or the null signal
+    /// to only check they exist.
+    ///
+    /// Without job control the processes are in the shell's own group, so
+    /// they're each sent the signal in turn.
+    pub fn signal(&self, signal: Option<Signal>) -> nix::Result<()> {
+        if session::enabled() {
+            killpg(self.pgid(), signal)
+        } else {
+            self.0.iter().try_for_each(|p| kill(p.pid, signal))
+        }
+    }
+
+    /// Wait for every process in the group to finish, or for the group to
+    /// stop, then take back the terminal if it was handed over.
+    ///
+    /// The group's status is the status of its last process, or how it was
+    /// stopped.
+    pub fn wait(&self) -> nix::Result<ExitStatus> {
+        let result = self.wait_untraced();
+        session::reclaim();
+        result
+    }
+
+    fn wait_untraced(&self) -> nix::Result<ExitStatus> {
+        let mut last = ExitStatus::SUCCESS;
+        for process in self.0.iter() {
+            match waitpid(Some(process.pid), Some(WaitPidFlag::WUNTRACED)) {
+                Ok(status) => match ExitStatus::try_from(status) {
+                    Ok(stopped @ ExitStatus::Stopped(_)) => return Ok(stopped),
+                    Ok(status) => last = status,
+                    Err(_) => {}
+                },
+                // Reaped already, while the rest of the group was stopped.
+                Err(Errno::ECHILD) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(last)
     }
 }
@@ -1,13 +1,101 @@
-/// Every process group is in a unique session.
+//! Job control, through the shell's session and its controlling terminal.
+//!
+//! Every process group is in a unique session.
+//!
+//! (When the process is created, it becomes a member of the session of its parent.) By convention,
+//! the session ID of a session equals the process ID of the first member of the session, called
+//! the session leader. A process finds the ID of its session using the system call getsid().
+//!
+//! Every session may have a controlling tty, that then also is called the controlling tty of each
+//! of its member processes. A file descriptor for the controlling tty is obtained by opening
+//! /dev/tty. (And when that fails, there was no controlling tty.) Given a file descriptor for the
+//! controlling tty, one may obtain the SID using tcgetsid(fd).
+//!
+//! With job control each job is put in a process group of its own, and the
+//! terminal is handed to whichever job is in the foreground. Only the
+//! foreground job gets the signals sent from the keyboard, like `SIGINT` for
+//! Ctrl-C and `SIGTSTP` for Ctrl-Z.
+use nix::{
+    sys::signal::{killpg, SigSet, Signal},
+    unistd::{getpgrp, getpid, setpgid, tcgetpgrp, tcsetpgrp, Pid},
+};
+use std::{
+    os::unix::io::RawFd,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// The controlling terminal, as the shell's standard input.
+const TERMINAL: RawFd = 0;
+
+// Is job control on?
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn on job control, putting the shell in its own process group in the
+/// foreground of the terminal.
 ///
-/// (When the process is created, it becomes a member of the session of its parent.) By convention,
-/// the session ID of a session equals the process ID of the first member of the session, called
-/// the session leader. A process finds the ID of its session using the system call getsid().
+/// This is for interactive shells, and must be done before the shell starts
+/// ignoring the signals which would stop it. Without a terminal job control
+/// stays off.
+pub fn init() -> nix::Result<()> {
+    // Wait until we're in the foreground, if we were started in the
+    // background.
+    loop {
+        let pgrp = getpgrp();
+        if tcgetpgrp(TERMINAL)? == pgrp {
+            break;
+        }
+        killpg(pgrp, Signal::SIGTTIN)?;
+    }
+
+    // Taking the terminal from our own new group would stop us otherwise.
+    let mut ttou = SigSet::empty();
+    ttou.add(Signal::SIGTTOU);
+    ttou.thread_block()?;
+    let pid = getpid();
+    // A session leader is already the leader of its group, and can't move.
+    let result = if getpgrp() == pid {
+        Ok(())
+    } else {
+        setpgid(pid, pid)
+    };
+    let result = result.and_then(|_| tcsetpgrp(TERMINAL, pid));
+    ttou.thread_unblock()?;
+
+    result?;
+    ENABLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Is job control on?
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Turn off job control, for the copies of the shell it forks.
+pub fn disable() {
+    ENABLED.store(false, Ordering::SeqCst);
+}
+
+/// Put the process in the group `pgid`, or a new group of its own.
 ///
-/// Every session may have a controlling tty, that then also is called the controlling tty of each
-/// of its member processes. A file descriptor for the controlling tty is obtained by opening
-/// /dev/tty. (And when that fails, there was no controlling tty.) Given a file descriptor for the
-/// controlling tty, one may obtain the SID using tcgetsid(fd).
-///
-#[allow(dead_code)]
-pub struct Session;
+/// This is done by both the shell and the child after forking, since there's
+/// no telling which will run first. Either one may lose the race, so errors
+/// are ignored. Returns the group's ID.
+pub fn join(pid: Pid, pgid: Option<Pid>) -> Pid {
+    let pgid = pgid.unwrap_or(pid);
+    setpgid(pid, pgid).ok();
+    pgid
+}
+
+/// Hand the terminal to the given process group.
+pub fn foreground(pgid: Pid) {
+    tcsetpgrp(TERMINAL, pgid).ok();
+}
+
+/// Take the terminal back for the shell, once its foreground job is done
+/// or stopped.
+pub fn reclaim() {
+    if enabled() {
+        foreground(getpgrp());
+    }
+}
@@ -1,3 +1,266 @@
-/// TODO: What signal handling can we put here?
-#[allow(dead_code)]
-pub struct Signal;
+//! Signal handling, for the shell itself and the commands it runs.
+//!
+//! Signals the shell catches are only recorded when they arrive, it's up to
+//! the shell to check for them with [`pending`] when it's safe to act on them.
+//! `SIGCHLD` is always caught, so the shell knows to check up on its jobs
+//! when [`children`] says so. To block until a signal arrives, like while
+//! `wait` waits for jobs, there's [`suspend`].
+//! The conditions of the `trap` builtin are signals, along with the shell's
+//! own exit.
+//!
+//! ```
+//! use oursh::process::signal::Condition;
+//! use nix::sys::signal::Signal;
+//!
+//! assert_eq!(Ok(Condition::Exit), "EXIT".parse());
+//! assert_eq!(Ok(Condition::Signal(Signal::SIGINT)), "int".parse());
+//! assert_eq!(Ok(Condition::Signal(Signal::SIGTERM)), "15".parse());
+//! assert_eq!("TERM", Condition::Signal(Signal::SIGTERM).to_string());
+//! ```
+use super::io::internal;
+use nix::{
+    fcntl::{fcntl, FcntlArg, OFlag},
+    libc::c_int,
+    poll::{poll, PollFd, PollFlags},
+    sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    unistd::{close, pipe, read, write},
+};
+use std::{
+    convert::TryFrom,
+    fmt,
+    os::unix::io::{BorrowedFd, RawFd},
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+    thread,
+    time::Duration,
+};
+
+// One more than the largest signal number there is a `Signal` for.
+const NSIG: usize = 32;
+
+// The signals which have arrived since they were last checked.
+static PENDING: [AtomicBool; NSIG] = [const { AtomicBool::new(false) }; NSIG];
+
+// The signals which are ignored, either from the start or by a trap.
+// Commands the shell runs ignore them too.
+static IGNORED: [AtomicBool; NSIG] = [const { AtomicBool::new(false) }; NSIG];
+
+// Has a child stopped, continued or finished since the last check?
+static CHILDREN: AtomicBool = AtomicBool::new(false);
+
+// The read and write ends of a pipe written to whenever a caught signal
+// arrives, for [`suspend`] to wake up on.
+static WAKE: [AtomicI32; 2] = [AtomicI32::new(-1), AtomicI32::new(-1)];
+
+// Is the shell interactive? Interactive shells aren't killed by `SIGINT`
+// or `SIGHUP`, or stopped by the signals of job control.
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+// The signals an interactive shell ignores for its own sake. The commands it
+// runs are left to stop like normal.
+const STOPS: [Signal; 3] = [Signal::SIGTSTP, Signal::SIGTTIN, Signal::SIGTTOU];
+
+/// Something the shell can trap, either a signal or its own exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Condition {
+    /// The shell is exiting, written `EXIT` or `0`.
+    Exit,
+    /// A signal, written by number or name, with or without the `SIG`
+    /// prefix.
+    Signal(Signal),
+}
+
+impl FromStr for Condition {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if let Ok(n) = text.parse::<i32>() {
+            return match n {
+                0 => Ok(Condition::Exit),
+                n => Signal::try_from(n).map(Condition::Signal).map_err(|_| ()),
+            };
+        }
+        let name = text.to_uppercase();
+        if name == "EXIT" {
+            return Ok(Condition::Exit);
+        }
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        Signal::iterator()
+            .find(|s| &s.as_str()[3..] == name)
+            .map(Condition::Signal)
+            .ok_or(())
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Exit => write!(f, "EXIT"),
+            Condition::Signal(signal) => write!(f, "{}", &signal.as_str()[3..]),
+        }
+    }
+}
+
+/// Set up the shell's own signal handling, before anything else is run.
+///
+/// Signals which were ignored when the shell started are remembered, so the
+/// commands it runs go on ignoring them. `SIGPIPE` is the exception, since
+/// it's ignored for every Rust program.
+pub fn init(interactive: bool) {
+    for signal in Signal::iterator() {
+        if signal == Signal::SIGPIPE {
+            continue;
+        }
+        if let Ok(action) = set(signal, SigHandler::SigDfl) {
+            if action.handler() == SigHandler::SigIgn {
+                set(signal, SigHandler::SigIgn).ok();
+                IGNORED[signal as usize].store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    wake();
+    catch(Signal::SIGCHLD).ok();
+    INTERACTIVE.store(interactive, Ordering::SeqCst);
+    if interactive {
+        catch(Signal::SIGINT).ok();
+        catch(Signal::SIGHUP).ok();
+        for signal in STOPS {
+            set(signal, SigHandler::SigIgn).ok();
+        }
+    }
+}
+
+/// Record the signal when it arrives, to be found by [`pending`].
+pub fn catch(signal: Signal) -> nix::Result<()> {
+    IGNORED[signal as usize].store(false, Ordering::SeqCst);
+    set(signal, SigHandler::Handler(record)).map(|_| ())
+}
+
+/// Ignore the signal, both in the shell and the commands it runs.
+///
+/// `SIGCHLD` is the exception, it's only ever caught since ignoring it would
+/// have the shell's children reaped behind its back.
+pub fn ignore(signal: Signal) -> nix::Result<()> {
+    if signal == Signal::SIGCHLD {
+        return catch(signal);
+    }
+    set(signal, SigHandler::SigIgn)?;
+    IGNORED[signal as usize].store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Go back to handling the signal the way the shell normally does.
+pub fn default(signal: Signal) -> nix::Result<()> {
+    IGNORED[signal as usize].store(false, Ordering::SeqCst);
+    let interactive = INTERACTIVE.load(Ordering::SeqCst);
+    let caught = [Signal::SIGINT, Signal::SIGHUP].contains(&signal) && interactive;
+    if caught || signal == Signal::SIGCHLD {
+        catch(signal)
+    } else if STOPS.contains(&signal) && interactive {
+        set(signal, SigHandler::SigIgn).map(|_| ())
+    } else {
+        set(signal, SigHandler::SigDfl).map(|_| ())
+    }
+}
+
+/// Take the signals which have arrived since the last call.
+pub fn pending() -> Vec<Signal> {
+    Signal::iterator()
+        .filter(|s| PENDING[*s as usize].swap(false, Ordering::SeqCst))
+        .collect()
+}
+
+/// The first signal other than `SIGCHLD` which has arrived and not yet been
+/// taken by [`pending`], if any. Signals are left to be taken.
+pub fn interrupted() -> Option<Signal> {
+    Signal::iterator()
+        .find(|s| *s != Signal::SIGCHLD && PENDING[*s as usize].load(Ordering::SeqCst))
+}
+
+/// Block until a signal the shell catches arrives, or return right away if
+/// one has arrived since the last call.
+pub fn suspend() {
+    let fd = WAKE[0].load(Ordering::SeqCst);
+    if fd < 0 {
+        // Without the pipe the best we can do is check back shortly.
+        thread::sleep(Duration::from_millis(10));
+        return;
+    }
+    // Safety: the pipe is only closed by `wake`, when it's replaced.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    poll(&mut [PollFd::new(&borrowed, PollFlags::POLLIN)], -1).ok();
+    let mut buffer = [0; 64];
+    while matches!(read(fd, &mut buffer), Ok(n) if n > 0) {}
+}
+
+/// Forget that the signal arrived, if it has since the last check.
+pub fn discard(signal: Signal) {
+    PENDING[signal as usize].store(false, Ordering::SeqCst);
+}
+
+/// Has any child of the shell stopped, continued or finished since the last
+/// call?
+pub fn children() -> bool {
+    CHILDREN.swap(false, Ordering::SeqCst)
+}
+
+/// Give a newly forked child the dispositions a command should start with.
+///
+/// Everything the shell catches goes back to its default, as does anything
+/// the shell ignores for its own sake, like `SIGPIPE`. Signals ignored by
+/// the user stay ignored.
+pub fn reset() {
+    INTERACTIVE.store(false, Ordering::SeqCst);
+    CHILDREN.store(false, Ordering::SeqCst);
+    // A pipe of its own, so it can't take wakeups meant for its parent.
+    wake();
+    for signal in Signal::iterator() {
+        if !IGNORED[signal as usize].load(Ordering::SeqCst) {
+            set(signal, SigHandler::SigDfl).ok();
+        }
+    }
+}
+
+fn set(signal: Signal, handler: SigHandler) -> nix::Result<SigAction> {
+    let action = SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty());
+    // Safety: the only handler installed is `record`, which is async signal
+    // safe.
+    unsafe { sigaction(signal, &action) }
+}
+
+// Open a new pipe for `suspend`, closing the old one.
+fn wake() {
+    for end in WAKE.iter() {
+        let fd = end.swap(-1, Ordering::SeqCst);
+        if fd >= 0 {
+            close(fd).ok();
+        }
+    }
+    let open = |fd: RawFd| -> nix::Result<RawFd> {
+        let internal = internal(fd)?;
+        fcntl(internal, FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+        Ok(internal)
+    };
+    if let Ok((reader, writer)) = pipe() {
+        if let (Ok(reader), Ok(writer)) = (open(reader), open(writer)) {
+            WAKE[0].store(reader, Ordering::SeqCst);
+            WAKE[1].store(writer, Ordering::SeqCst);
+        }
+    }
+}
+
+extern "C" fn record(signal: c_int) {
+    if let Some(pending) = PENDING.get(signal as usize) {
+        pending.store(true, Ordering::SeqCst);
+    }
+    if signal == Signal::SIGCHLD as c_int {
+        CHILDREN.store(true, Ordering::SeqCst);
+    }
+    // The write only fails, and so only changes `errno`, when the pipe is
+    // already full of wakeups.
+    let fd = WAKE[1].load(Ordering::SeqCst);
+    if fd >= 0 {
+        write(fd, &[0]).ok();
+    }
+}
@@ -0,0 +1,81 @@
+use nix::sys::{signal::Signal, wait::WaitStatus};
+use std::{convert::TryFrom, os::unix::process::ExitStatusExt, process};
+
+/// The outcome of running a command.
+///
+/// This is what `$?`, `&&` and `||`, and the shell's own exit code are all
+/// derived from. Unlike a raw [`WaitStatus`] it doesn't carry a process ID,
+/// since plenty of commands (builtins for example) never have one.
+///
+/// ```
+/// use nix::sys::signal::Signal;
+/// use oursh::process::ExitStatus;
+///
+/// assert!(ExitStatus::Exited(0).success());
+/// assert_eq!(2, ExitStatus::Exited(2).code());
+/// assert_eq!(130, ExitStatus::Signaled(Signal::SIGINT, false).code());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The command finished, with the given code.
+    Exited(i32),
+    /// The command was killed by a signal, possibly dumping core.
+    Signaled(Signal, bool),
+    /// The command was stopped by a signal and may yet be continued.
+    Stopped(Signal),
+}
+
+impl ExitStatus {
+    /// The status of a command which finished without issue.
+    pub const SUCCESS: ExitStatus = ExitStatus::Exited(0);
+
+    /// The numeric status, as it's seen by `$?`.
+    ///
+    /// Commands terminated or stopped by a signal report `128` plus the
+    /// signal's number.
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitStatus::Exited(code) => *code,
+            ExitStatus::Signaled(signal, _) | ExitStatus::Stopped(signal) => 128 + *signal as i32,
+        }
+    }
+
+    /// Was this command successful?
+    pub fn success(&self) -> bool {
+        self.code() == 0
+    }
+}
+
+impl Default for ExitStatus {
+    fn default() -> Self {
+        ExitStatus::SUCCESS
+    }
+}
+
+/// Only statuses of commands which are done running (at least for now)
+/// convert, anything else is handed back.
+impl TryFrom<WaitStatus> for ExitStatus {
+    type Error = WaitStatus;
+
+    fn try_from(status: WaitStatus) -> Result<Self, Self::Error> {
+        match status {
+            WaitStatus::Exited(_, code) => Ok(ExitStatus::Exited(code)),
+            WaitStatus::Signaled(_, signal, core) => Ok(ExitStatus::Signaled(signal, core)),
+            WaitStatus::Stopped(_, signal) => Ok(ExitStatus::Stopped(signal)),
+            status => Err(status),
+        }
+    }
+}
+
+impl From<process::ExitStatus> for ExitStatus {
+    fn from(status: process::ExitStatus) -> Self {
+        let signal = |n| Signal::try_from(n).expect("valid signal number");
+        if let Some(n) = status.signal() {
+            ExitStatus::Signaled(signal(n), status.core_dumped())
+        } else if let Some(n) = status.stopped_signal() {
+            ExitStatus::Stopped(signal(n))
+        } else {
+            ExitStatus::Exited(status.code().unwrap_or_default())
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! The CPU time used by the shell and the commands it runs, as reported by
+//! `time` and `times`.
+//!
+//! ```
+//! use oursh::process::usage::{minutes, seconds};
+//! use std::time::Duration;
+//!
+//! assert_eq!("1m2.346s", minutes(Duration::from_millis(62346)));
+//! assert_eq!("62.35", seconds(Duration::from_millis(62346)));
+//! ```
+use nix::{
+    errno::Errno,
+    libc,
+    sys::{
+        resource::{getrusage, UsageWho},
+        time::{TimeVal, TimeValLike},
+        wait::{WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+use std::{
+    mem::MaybeUninit,
+    ops::{Add, AddAssign, Sub},
+    time::Duration,
+};
+
+/// Time spent running in user mode, and by the system on a process's
+/// behalf.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub user: Duration,
+    pub system: Duration,
+}
+
+impl Usage {
+    /// The shell's own usage so far.
+    pub fn shell() -> Self {
+        Usage::of(UsageWho::RUSAGE_SELF)
+    }
+
+    /// The usage of all of the shell's children which have been waited
+    /// for.
+    pub fn children() -> Self {
+        Usage::of(UsageWho::RUSAGE_CHILDREN)
+    }
+
+    fn of(who: UsageWho) -> Self {
+        getrusage(who).map_or_else(
+            |_| Usage::default(),
+            |usage| Usage {
+                user: duration(usage.user_time()),
+                system: duration(usage.system_time()),
+            },
+        )
+    }
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(self, other: Usage) -> Usage {
+        Usage {
+            user: self.user + other.user,
+            system: self.system + other.system,
+        }
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Usage {
+    type Output = Usage;
+
+    fn sub(self, other: Usage) -> Usage {
+        Usage {
+            user: self.user.saturating_sub(other.user),
+            system: self.system.saturating_sub(other.system),
+        }
+    }
+}
+
+/// Wait on a child like `waitpid`, also giving the time it used once it
+/// has finished.
+pub fn wait(pid: Pid, flags: Option<WaitPidFlag>) -> nix::Result<(WaitStatus, Usage)> {
+    let mut status = 0;
+    let mut rusage = MaybeUninit::<libc::rusage>::zeroed();
+    let flags = flags.map_or(0, |flags| flags.bits());
+    // Safety: both pointers are to memory we own, big enough for their
+    // types.
+    let reaped = Errno::result(unsafe {
+        libc::wait4(pid.as_raw(), &mut status, flags, rusage.as_mut_ptr())
+    })?;
+    if reaped == 0 {
+        return Ok((WaitStatus::StillAlive, Usage::default()));
+    }
+    // Safety: zeroed is a valid `rusage`, and `wait4` only fills it in.
+    let rusage = unsafe { rusage.assume_init() };
+    let usage = Usage {
+        user: duration(TimeVal::from(rusage.ru_utime)),
+        system: duration(TimeVal::from(rusage.ru_stime)),
+    };
+    Ok((WaitStatus::from_raw(Pid::from_raw(reaped), status)?, usage))
+}
+
+/// The duration in minutes and seconds, the way `time` and `times` write
+/// it.
+pub fn minutes(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    let whole = (seconds / 60.0).floor();
+    format!("{}m{:.3}s", whole, seconds - whole * 60.0)
+}
+
+/// The duration in seconds, the way `time -p` writes it.
+pub fn seconds(duration: Duration) -> String {
+    format!("{:.2}", duration.as_secs_f64())
+}
+
+fn duration(time: TimeVal) -> Duration {
+    Duration::from_micros(time.num_microseconds().max(0) as u64)
+}
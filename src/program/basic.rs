@@ -1,9 +1,8 @@
 //! Single command programs with no features.
 use crate::{
-    process::{Process, ProcessGroup, Wait},
+    process::{ExitStatus, Process, ProcessGroup},
     program::{Error, Result, Runtime},
 };
-use nix::sys::wait::WaitStatus;
 use std::{ffi::CString, io::BufRead};
 
 /// A basic program with only a single command.
@@ -22,9 +21,7 @@ impl super::Program for Program {
     /// ```
     fn parse<R: BufRead>(mut reader: R) -> Result<Self> {
         let mut command = String::new();
-        if reader.read_to_string(&mut command).is_err() {
-            return Err(Error::Read);
-        }
+        reader.read_to_string(&mut command).map_err(Error::Read)?;
         Ok(Program(vec![Command(command)]))
     }
 
@@ -41,28 +38,22 @@ pub struct Command(String);
 impl super::Command for Command {}
 
 impl super::Run for Command {
-    fn run(&self, runtime: &mut Runtime) -> Result<WaitStatus> {
-        let argv = self
+    fn run(&self, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let argv: Vec<CString> = self
             .0
             .split_whitespace()
             .map(|a| CString::new(a).expect("error reading argument"))
             .collect();
-
-        let status = if runtime.background {
-            let job = Process::fork(argv, runtime.io).map_err(|_| Error::Runtime)?;
-            let status = job.status();
-            runtime
-                .jobs
-                .borrow_mut()
-                .push(("???".into(), ProcessGroup(job)));
-            status
+        let failed = |e| Error::failed(argv[0].to_string_lossy(), e);
+
+        let foreground = !runtime.background;
+        let job = Process::fork(argv.clone(), &runtime.io, None, foreground).map_err(failed)?;
+        if runtime.background {
+            let id = runtime.jobs.add(self.0.clone(), ProcessGroup::new(job));
+            runtime.jobs.backgrounded(id);
+            Ok(ExitStatus::SUCCESS)
         } else {
-            let job = Process::fork(argv, runtime.io).map_err(|_| Error::Runtime)?;
-            job.wait()
-        };
-        match status {
-            Ok(WaitStatus::Exited(p, c)) if c == 0 => Ok(WaitStatus::Exited(p, c)),
-            _ => Err(Error::Runtime),
+            ProcessGroup::new(job).wait().map_err(failed)
         }
     }
 }
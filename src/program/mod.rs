@@ -55,33 +55,366 @@
 //!
 //! Strict POSIX compatibility can be enabled by removing this feature alone.
 //!
-//! - TODO #5: Parse sequence of programs from stream.
-//! - TODO #5: Partial parses for readline-ish / syntax highlighting.
+//! ### Partial Programs
+//!
+//! A program which ends inside a construct, like a quote or an `if`, isn't
+//! wrong, it's unfinished. Parsing one gives an
+//! [`Incomplete`](Error::Incomplete) error saying what's still [`Open`], so
+//! the rest can be read before running it.
 
-use crate::process::jobs;
-use nix::{sys::wait::WaitStatus, unistd::Pid};
-use std::{ffi::CString, fmt::Debug, io::BufRead, result};
+use crate::process::{
+    jobs::State,
+    session,
+    signal::{self, Condition},
+    ExitStatus,
+};
+use crate::program::posix::ast::Span;
+use lalrpop_util::ParseError;
+use nix::{errno::Errno, sys::signal::Signal};
+use std::{
+    error,
+    ffi::CString,
+    fmt::{self, Debug},
+    io::{self, BufRead},
+    marker::PhantomData,
+    process, result,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 /// Convenience type for results with program errors.
 pub type Result<T> = result::Result<T, Error>;
 
+/// A single error found by a lexer or parser.
+///
+/// Tokens are given by their text in the program.
+pub type SyntaxError = ParseError<usize, String, posix::lex::Error>;
+
 /// A comprehensive error type for the operation of programs.
+///
+/// Nothing is printed when these are created, it's up to the caller to
+/// report them (the `Display` output is meant for users), and to carry on
+/// with [`status`](Error::status) as the result of the failed program.
+/// Commands which fail as they run are handed to the runtime's
+/// [`report`](Runtime::report) as they fail, so the rest of the program
+/// can carry on.
 #[derive(Debug)]
 pub enum Error {
     /// A general issue reading the program.
-    // TODO: Wrap an io error?
-    Read,
-    /// An error within the lexer or parser.
-    // TODO: Wrap both our lex::Error and ParseError.
-    Parse,
+    Read(io::Error),
+    /// Errors within the lexer or parser, as many as could be found.
+    Parse(Vec<SyntaxError>),
+    /// The program ended with a construct still open, more input may yet
+    /// complete it.
+    Incomplete(Open),
     /// An error encountered during the evaluation of a program.
-    // TODO: Propagate status.
-    // TODO: Just wrap an Wait/ExitStatus?
-    Runtime,
+    Runtime {
+        /// The name of the command which failed.
+        command: String,
+        /// The status this failure gives the command.
+        status: ExitStatus,
+        /// The underlying cause, if there is one.
+        source: Option<io::Error>,
+    },
+}
+
+impl Error {
+    /// The error of a command which failed because of a system error, with
+    /// a status of 1.
+    ///
+    /// ```
+    /// use nix::errno::Errno;
+    /// use oursh::program::Error;
+    ///
+    /// let error = Error::failed("cd", Errno::ENOENT);
+    /// assert_eq!("cd: No such file or directory", error.to_string());
+    /// ```
+    pub fn failed(command: impl Into<String>, errno: Errno) -> Self {
+        Error::Runtime {
+            command: command.into(),
+            status: ExitStatus::Exited(1),
+            source: Some(io::Error::from_raw_os_error(errno as i32)),
+        }
+    }
+
+    /// The status of a program which failed with this error.
+    pub fn status(&self) -> ExitStatus {
+        match self {
+            Error::Read(_) => ExitStatus::Exited(1),
+            Error::Parse(_) | Error::Incomplete(_) => ExitStatus::Exited(2),
+            Error::Runtime { status, .. } => *status,
+        }
+    }
+
+    /// Render this error for the user, pointing into the `text` of the
+    /// program it came from, named `name`.
+    ///
+    /// There is one diagnostic for each problem found.
+    ///
+    /// ```
+    /// use oursh::program::parse_primary;
+    ///
+    /// let text = "echo 1\necho )";
+    /// let error = parse_primary(text.as_bytes()).unwrap_err();
+    /// assert_eq!(error.diagnostics("-c", text), vec!["\
+    /// -c:2:6: unexpected token `)`
+    /// echo )
+    ///      ^ expected one of `;` or newline"]);
+    /// ```
+    pub fn diagnostics(&self, name: &str, text: &str) -> Vec<String> {
+        self.diagnostics_from(name, text, 1)
+    }
+
+    /// Render this error like [`diagnostics`](Error::diagnostics), for
+    /// `text` which starts on the given `line` of the program, as it does
+    /// when parsing a stream.
+    pub fn diagnostics_from(&self, name: &str, text: &str, line: usize) -> Vec<String> {
+        match self {
+            Error::Read(_) => vec![format!("{}: {}", name, self)],
+            Error::Parse(errors) => errors
+                .iter()
+                .map(|e| diagnostic(e, name, text, line))
+                .collect(),
+            Error::Incomplete(open) => vec![render(
+                open.span,
+                &open.construct.to_string(),
+                None,
+                name,
+                text,
+                line,
+            )],
+            Error::Runtime { .. } => vec![self.to_string()],
+        }
+    }
+}
+
+/// Where a program was left unfinished, see [`Error::Incomplete`].
+///
+/// ```
+/// use oursh::program::{parse_primary, Construct, Error};
+///
+/// match parse_primary(b"if true; then\n  echo 1 |" as &[u8]) {
+///     Err(Error::Incomplete(open)) => assert_eq!(Construct::Pipe, open.construct),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Open {
+    /// The innermost construct still open at the end of the program.
+    pub construct: Construct,
+    /// Where the construct was opened.
+    pub span: Span,
+}
+
+/// A construct which can be left [`Open`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Construct {
+    /// A word quoted with the given quote.
+    Quote(char),
+    /// An `if` without its `fi`.
+    If,
+    /// A `{` group without its `}`.
+    Brace,
+    /// A `$(` substitution without its `)`.
+    Subshell,
+    /// A `{#` language block without its `}`.
+    Lang,
+    /// A pipeline with nothing after its `|`.
+    Pipe,
+    /// An `&&` with nothing after it.
+    And,
+    /// An `||` with nothing after it.
+    Or,
+}
+
+impl fmt::Display for Construct {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Construct::Quote(c) => write!(f, "unterminated quote `{}`", c),
+            Construct::If => write!(f, "unterminated `if`"),
+            Construct::Brace => write!(f, "unterminated `{{`"),
+            Construct::Subshell => write!(f, "unterminated `(`"),
+            Construct::Lang => write!(f, "unterminated `{{#`"),
+            Construct::Pipe => write!(f, "missing command after `|`"),
+            Construct::And => write!(f, "missing command after `&&`"),
+            Construct::Or => write!(f, "missing command after `||`"),
+        }
+    }
+}
+
+// Show where in the text a syntax error is, and what was expected there.
+fn diagnostic(error: &SyntaxError, name: &str, text: &str, first_line: usize) -> String {
+    let (span, message, expected) = match error {
+        ParseError::InvalidToken { location } => (
+            Span::new(*location, *location + 1),
+            "invalid token".into(),
+            None,
+        ),
+        ParseError::UnrecognizedToken {
+            token: (s, t, e),
+            expected,
+        } => (Span::new(*s, *e), unexpected(t), Some(expected)),
+        ParseError::UnrecognizedEof { location, expected } => (
+            Span::new(*location, *location),
+            "unexpected end of input".into(),
+            Some(expected),
+        ),
+        ParseError::ExtraToken { token: (s, t, e) } => (Span::new(*s, *e), unexpected(t), None),
+        ParseError::User {
+            error: posix::lex::Error::UnrecognizedChar(s, c, e),
+        } => (
+            Span::new(*s, *e),
+            format!("unexpected character `{}`", c),
+            None,
+        ),
+        ParseError::User {
+            error: posix::lex::Error::UnterminatedQuote(s, c, _),
+        } => (
+            Span::new(*s, *s + 1),
+            Construct::Quote(*c).to_string(),
+            None,
+        ),
+    };
+    render(span, &message, expected, name, text, first_line)
+}
+
+// Point at the span of the text, with a message.
+fn render(
+    span: Span,
+    message: &str,
+    expected: Option<&Vec<String>>,
+    name: &str,
+    text: &str,
+    first_line: usize,
+) -> String {
+    let (line, column) = span.location(text);
+    let position = span.position(name, text, first_line);
+    let source = text.lines().nth(line - 1).unwrap_or_default();
+    // Line the caret up, keeping any tabs so it matches the source.
+    let indent: String = source
+        .chars()
+        .take(column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = text[span.start.min(text.len())..span.end.min(text.len())]
+        .lines()
+        .next()
+        .map_or(0, |s| s.chars().count())
+        .max(1);
+    let mut diagnostic = format!(
+        "{}: {}\n{}\n{}{}",
+        position,
+        message,
+        source,
+        indent,
+        "^".repeat(width)
+    );
+    if let Some(expected) = expected.filter(|e| !e.is_empty()) {
+        diagnostic += &format!(" expected {}", one_of(expected));
+    }
+    diagnostic
+}
+
+// Describe an unexpected token by its text.
+fn unexpected(token: &str) -> String {
+    match token {
+        "\n" => "unexpected newline".into(),
+        t => format!("unexpected token `{}`", t),
+    }
+}
+
+// Describe the terminals LALRPOP expected, as a user would write them.
+fn one_of(expected: &[String]) -> String {
+    let mut names: Vec<String> = expected
+        .iter()
+        .map(|e| match e.trim_matches('"') {
+            "\\n" => "newline".into(),
+            "WORD" => "a word".into(),
+            "ASSIGNMENT" => "an assignment".into(),
+            "IO_NUMBER" => "a file descriptor".into(),
+            "TEXT" => "text".into(),
+            t => format!("`{}`", t.replace("\\\\", "\\").replace("\\\"", "\"")),
+        })
+        .collect();
+    // List the literal tokens before the kinds of token.
+    names.sort_by_key(|n| !n.starts_with('`'));
+    match names.pop() {
+        Some(last) if names.is_empty() => last,
+        Some(last) => format!("one of {} or {}", names.join(", "), last),
+        None => String::new(),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Read(e) => write!(f, "error reading program: {}", e),
+            Error::Parse(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    match error {
+                        ParseError::InvalidToken { location } => {
+                            write!(f, "invalid token found at {}", location)?
+                        }
+                        ParseError::UnrecognizedToken {
+                            token: (s, t, e),
+                            expected,
+                        } => write!(
+                            f,
+                            "unexpected token {:?} found at {}-{}, expecting one of: {}",
+                            t,
+                            s,
+                            e,
+                            expected.join(", ")
+                        )?,
+                        ParseError::UnrecognizedEof { location, expected } => write!(
+                            f,
+                            "unexpected EOF found at {}, expecting one of: {}",
+                            location,
+                            expected.join(", ")
+                        )?,
+                        ParseError::ExtraToken { token: (s, t, _) } => {
+                            write!(f, "extra token {:?} found at {}", t, s)?
+                        }
+                        ParseError::User { error } => write!(f, "{}", error)?,
+                    }
+                }
+                Ok(())
+            }
+            Error::Incomplete(open) => write!(f, "{} found at {}", open.construct, open.span.start),
+            Error::Runtime {
+                command,
+                source: Some(e),
+                ..
+            } => match e.raw_os_error() {
+                // Without the number of the error, like `sh`.
+                Some(errno) => write!(f, "{}: {}", command, Errno::from_i32(errno).desc()),
+                None => write!(f, "{}: {}", command, e),
+            },
+            Error::Runtime {
+                command, status, ..
+            } => write!(f, "{}: failed with status {}", command, status.code()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Read(e) => Some(e),
+            Error::Parse(errors) => errors.iter().find_map(|e| match e {
+                ParseError::User { error } => Some(error as _),
+                _ => None,
+            }),
+            Error::Incomplete(_) => None,
+            Error::Runtime { source, .. } => source.as_ref().map(|e| e as _),
+        }
+    }
 }
 
 pub trait Run {
-    fn run(&self, runtime: &mut Runtime) -> Result<WaitStatus>;
+    fn run(&self, runtime: &mut Runtime) -> Result<ExitStatus>;
 }
 
 /// A program is as large as a file or as small as a line.
@@ -107,10 +440,11 @@ pub trait Program: Sized + Debug + Run {
 }
 
 impl<P: Program> Run for P {
-    fn run(&self, runtime: &mut Runtime) -> Result<WaitStatus> {
-        let mut last = WaitStatus::Exited(Pid::this(), 0);
+    fn run(&self, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let mut last = ExitStatus::SUCCESS;
         for command in self.commands().iter() {
             last = command.run(runtime)?;
+            *runtime.status = last;
         }
         Ok(last)
     }
@@ -182,13 +516,101 @@ pub fn parse<P: Program, R: BufRead>(reader: R) -> Result<P> {
     P::parse(reader)
 }
 
+/// Parse programs of the given type from a stream, each as soon as it's
+/// complete.
+///
+/// Input is read a line at a time, until what's been read is either a whole
+/// program, or broken in a way no more input could fix. This way each
+/// program can be run before the stream has even been fully written.
+///
+/// # Examples
+///
+/// ```
+/// use oursh::program::{parse_stream, PosixProgram, Program};
+///
+/// let stream = b"echo 1 |\nwc\n\necho 2" as &[u8];
+/// let mut programs = parse_stream::<PosixProgram, _>(stream);
+/// let first = programs.next().unwrap();
+/// assert_eq!("echo 1 |\nwc\n", first.text);
+/// assert_eq!(1, first.program.unwrap().commands().len());
+/// assert_eq!(4, programs.next().unwrap().line);
+/// assert!(programs.next().is_none());
+/// ```
+pub fn parse_stream<P: Program, R: BufRead>(reader: R) -> Programs<P, R> {
+    Programs {
+        reader,
+        line: 1,
+        program: PhantomData,
+    }
+}
+
+/// An iterator over the programs of a stream, from [`parse_stream`].
+#[derive(Debug)]
+pub struct Programs<P, R> {
+    reader: R,
+    line: usize,
+    program: PhantomData<P>,
+}
+
+/// A single program read from a stream.
+#[derive(Debug)]
+pub struct Parsed<P> {
+    /// The text of the program, as it was read.
+    pub text: String,
+    /// The line of the stream the text starts on, counting from 1.
+    pub line: usize,
+    /// The program itself, or why it isn't one.
+    pub program: Result<P>,
+}
+
+impl<P: Program, R: BufRead> Iterator for Programs<P, R> {
+    type Item = Parsed<P>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut text = String::new();
+        loop {
+            let eof = match self.reader.read_line(&mut text) {
+                Ok(0) if text.is_empty() => return None,
+                Ok(n) => n == 0,
+                Err(e) => {
+                    let line = self.line;
+                    self.line += text.matches('\n').count();
+                    return Some(Parsed {
+                        text,
+                        line,
+                        program: Err(Error::Read(e)),
+                    });
+                }
+            };
+
+            let program = match P::parse(text.as_bytes()) {
+                Err(Error::Incomplete(_)) if !eof => continue,
+                // Blank lines and comments aren't worth a program.
+                Ok(program) if program.commands().is_empty() => {
+                    self.line += text.matches('\n').count();
+                    text.clear();
+                    continue;
+                }
+                program => program,
+            };
+            let line = self.line;
+            self.line += text.matches('\n').count();
+            return Some(Parsed {
+                text,
+                line,
+                program,
+            });
+        }
+    }
+}
+
 // The various program grammars.
 //
 // If reading this code were like sking, you'd now be hitting blues. ASTs and
 // language semantics are somewhat tricky subjects.
 
 pub mod runtime;
-pub use self::runtime::Runtime;
+pub use self::runtime::{Aliases, Options, Runtime, Traps};
 
 pub mod basic;
 pub use self::basic::Program as BasicProgram;
@@ -197,19 +619,67 @@ pub use self::posix::Program as PosixProgram;
 pub mod modern;
 pub use self::modern::Program as ModernProgram;
 
+/// Parse and run a program of the primary type, reporting any errors.
+///
+/// The `name` of the program, for example the file it was read from, is
+/// used in diagnostics.
 // TODO: Replace program::Result
-pub fn parse_and_run(text: &str, runtime: &mut Runtime) -> crate::program::Result<WaitStatus> {
+pub fn parse_and_run(
+    name: &str,
+    text: &str,
+    runtime: &mut Runtime,
+) -> crate::program::Result<ExitStatus> {
+    expand_and_run(name, text, 1, runtime)
+}
+
+/// Parse and run programs of the primary type from a stream, running each
+/// as soon as it's complete.
+///
+/// Like [`parse_and_run`] any errors are reported. A program which fails
+/// as it runs only sets `$?`, the programs after it still run, but like a
+/// script which won't parse, nothing more is run after a syntax error.
+pub fn parse_and_run_stream<R: BufRead>(
+    name: &str,
+    reader: R,
+    runtime: &mut Runtime,
+) -> crate::program::Result<ExitStatus> {
+    let mut status = ExitStatus::SUCCESS;
+    for parsed in parse_stream::<PrimaryProgram, _>(reader) {
+        status = match parsed.program {
+            // Aliases may change the program, now that it's time to run it.
+            Ok(_) => match expand_and_run(name, &parsed.text, parsed.line, runtime) {
+                Ok(status) => status,
+                Err(e @ (Error::Parse(_) | Error::Incomplete(_))) => return Err(e),
+                // Already reported, with `$?` set.
+                Err(e) => e.status(),
+            },
+            Err(e) => return Err(report(e, name, &parsed.text, parsed.line, runtime)),
+        };
+    }
+    Ok(status)
+}
+
+// Parse a program, `text` starting on `line`, substituting the aliases as
+// it's lexed, then run it.
+fn expand_and_run(
+    name: &str,
+    text: &str,
+    line: usize,
+    runtime: &mut Runtime,
+) -> Result<ExitStatus> {
     // Parse with the primary grammar and run each command in order.
-    let program = match parse_primary(text.as_bytes()) {
-        Ok(program) => program,
-        Err(e) => {
-            eprintln!("{:?}: {:#?}", e, text);
-            return Err(e);
-        }
-    };
+    match PrimaryProgram::parse_aliased(text, runtime.aliases) {
+        Ok(program) => run(text, &program, runtime),
+        Err(e) => Err(report(e, name, text, line, runtime)),
+    }
+}
 
+// Run a parsed program, reporting any error.
+fn run(text: &str, program: &PrimaryProgram, runtime: &mut Runtime) -> Result<ExitStatus> {
     #[cfg(feature = "history")]
     runtime.history.add(text, 1);
+    #[cfg(not(feature = "history"))]
+    let _ = text;
 
     // Print the program if the flag is given.
     if runtime.args.get_bool("--ast") {
@@ -217,8 +687,137 @@ pub fn parse_and_run(text: &str, runtime: &mut Runtime) -> crate::program::Resul
     }
 
     // Run it!
+    let warned = WARNED.load(Ordering::SeqCst);
     let result = program.run(runtime);
-    // Check up on the background jobs.
-    jobs::retain_alive(runtime.jobs);
+    // Only the very next attempt to exit after the warning goes ahead.
+    if warned {
+        WARNED.store(false, Ordering::SeqCst);
+    }
+    if let Err(ref e) = result {
+        (runtime.report)(e);
+        *runtime.status = e.status();
+    }
+    // Report the jobs which stopped or finished, before the next prompt.
+    update_jobs(runtime);
+    runtime.jobs.notify();
+    runtime.jobs.reap_helpers();
     result
 }
+
+// Is a trap running? Traps aren't run from within another trap.
+static TRAPPING: AtomicBool = AtomicBool::new(false);
+
+/// Run the traps of any signals which have arrived since the last check.
+///
+/// This is only done between commands, when it's safe to run more, and not
+/// while another trap is running.
+pub fn run_traps(runtime: &mut Runtime) {
+    if TRAPPING.load(Ordering::SeqCst) {
+        return;
+    }
+    for signal in signal::pending() {
+        match runtime.traps.get(&Condition::Signal(signal)) {
+            Some(action) => {
+                let action = action.clone();
+                run_trap(&action, runtime);
+                // The trap's own commands are children of the shell too,
+                // they mustn't set it off again.
+                if signal == Signal::SIGCHLD {
+                    signal::discard(signal);
+                }
+            }
+            // An interactive shell only moves on to a new line.
+            None if signal == Signal::SIGINT => println!(),
+            None if signal == Signal::SIGHUP => hangup(runtime),
+            None => {}
+        }
+    }
+}
+
+/// Check up on the jobs, if any of the shell's children have changed since
+/// the last check.
+///
+/// Like traps this is done between commands. Jobs which stopped or finished
+/// are reported right away with `set -b`, otherwise they're left to be
+/// reported before the next prompt.
+pub fn update_jobs(runtime: &mut Runtime) {
+    if signal::children() {
+        runtime.jobs.poll();
+    }
+    if runtime.options.notify {
+        runtime.jobs.notify();
+    }
+}
+
+/// Hang up, as an interactive shell does when its terminal goes away.
+///
+/// The jobs are sent `SIGHUP` too, then the shell exits once its `EXIT`
+/// trap has run.
+pub fn hangup(runtime: &mut Runtime) -> ! {
+    #[cfg(feature = "history")]
+    runtime.history.save_or_report();
+    runtime.jobs.hangup();
+    run_exit_trap(runtime);
+    process::exit(128 + Signal::SIGHUP as i32)
+}
+
+// Has the shell warned that it has stopped jobs, since the last program
+// which didn't try to exit?
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// May the shell exit? With job control, the first attempt to exit while
+/// there are stopped jobs only warns about them. Trying again straight
+/// after exits anyway.
+pub fn may_exit(runtime: &mut Runtime) -> bool {
+    if !session::enabled() || WARNED.load(Ordering::SeqCst) {
+        return true;
+    }
+    let stopped = runtime
+        .jobs
+        .iter()
+        .any(|job| matches!(job.state(), State::Stopped(_)));
+    if stopped {
+        eprintln!("There are stopped jobs.");
+        WARNED.store(true, Ordering::SeqCst);
+    }
+    !stopped
+}
+
+/// Run the `EXIT` trap, if there is one, as the shell exits.
+///
+/// The trap is removed first, so exiting from within it is final.
+pub fn run_exit_trap(runtime: &mut Runtime) {
+    if let Some(action) = runtime.traps.remove(&Condition::Exit) {
+        run_trap(&action, runtime);
+    }
+}
+
+// Run the action of a trap, leaving `$?` as it was. Unlike the programs the
+// user enters, traps aren't added to the history.
+fn run_trap(action: &str, runtime: &mut Runtime) {
+    let status = *runtime.status;
+    let trapping = TRAPPING.swap(true, Ordering::SeqCst);
+    match PrimaryProgram::parse_aliased(action, runtime.aliases) {
+        Ok(program) => {
+            if let Err(e) = program.run(runtime) {
+                (runtime.report)(&e);
+            }
+        }
+        Err(e) => {
+            for diagnostic in e.diagnostics_from("trap", action, 1) {
+                eprintln!("oursh: {}", diagnostic);
+            }
+        }
+    }
+    TRAPPING.store(trapping, Ordering::SeqCst);
+    *runtime.status = status;
+}
+
+// Report a program which couldn't be parsed, `text` starting on `line`.
+fn report(e: Error, name: &str, text: &str, line: usize, runtime: &mut Runtime) -> Error {
+    for diagnostic in e.diagnostics_from(name, text, line) {
+        eprintln!("oursh: {}", diagnostic);
+    }
+    *runtime.status = e.status();
+    e
+}
@@ -0,0 +1,117 @@
+//! Alias substitution, done to the tokens of a program as it's lexed.
+//!
+//! ```
+//! use oursh::program::{posix::{alias::Aliased, lex::Token}, Aliases};
+//!
+//! let mut aliases = Aliases::new();
+//! aliases.insert("ll".into(), "ls -l".into());
+//! let tokens: Vec<_> = Aliased::new("ll | wc", &aliases)
+//!     .map(|token| token.unwrap())
+//!     .collect();
+//! assert_eq!((0, Token::Word("ls"), 2), tokens[0]);
+//! assert_eq!((0, Token::Word("-l"), 2), tokens[1]);
+//! assert_eq!((3, Token::Pipe, 4), tokens[2]);
+//! ```
+use crate::program::{
+    posix::lex::{Error, Lexer, Span, Token},
+    Aliases,
+};
+
+/// The tokens of a program, with the name of each simple command which is
+/// an alias replaced by the tokens of the alias' value.
+///
+/// As in POSIX, the value of an alias is itself expanded, though never by
+/// the same alias again. When a value ends with a blank the word following
+/// it is checked for an alias too, even as an argument.
+///
+/// The tokens of a value are given the location of the alias' name in the
+/// program, so errors in them point at what was written.
+pub struct Aliased<'a> {
+    aliases: &'a Aliases,
+    // The lexer of the program, then the lexer of each alias' value being
+    // substituted, with the text each lexes.
+    lexers: Vec<(Lexer<'a>, &'a str)>,
+    // The aliases being substituted, with where their names are in the
+    // program.
+    expanding: Vec<(&'a str, usize, usize)>,
+    // Did the value of the last alias end in a blank?
+    blank: bool,
+}
+
+impl<'a> Aliased<'a> {
+    /// Lex the text of a program, substituting aliases.
+    pub fn new(text: &'a str, aliases: &'a Aliases) -> Self {
+        Aliased {
+            aliases,
+            lexers: vec![(Lexer::new(text), text)],
+            expanding: vec![],
+            blank: false,
+        }
+    }
+
+    // The value of the alias the word at `s..e` names, if it's an alias
+    // written as is, not quoted in any way, and not already being
+    // substituted. The alias is then being substituted.
+    fn alias(&mut self, word: &'a str, s: usize, e: usize) -> Option<&'a str> {
+        let value = self.aliases.get(word)?;
+        let (_, text) = self.lexers.last().expect("a lexer");
+        if &text[s..e] != word || self.expanding.iter().any(|(name, ..)| *name == word) {
+            return None;
+        }
+        self.expanding.push((word, s, e));
+        Some(value)
+    }
+}
+
+impl<'a> Iterator for Aliased<'a> {
+    type Item = Span<Token<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (lexer, _) = self.lexers.last_mut().expect("the program's lexer");
+            let command = lexer.command_position() || self.blank;
+            let token = match lexer.next() {
+                // The end of an alias' value, back to what came after its
+                // name.
+                None if !self.expanding.is_empty() => {
+                    let (done, value) = self.lexers.pop().expect("the value's lexer");
+                    self.lexers
+                        .last_mut()
+                        .expect("the program's lexer")
+                        .0
+                        .continue_from(&done);
+                    self.expanding.pop();
+                    self.blank = value.ends_with([' ', '\t']);
+                    continue;
+                }
+                None => return None,
+                Some(token) => token,
+            };
+            self.blank = false;
+
+            let token = match token {
+                Ok((s, Token::Word(word), e)) if command => match self.alias(word, s, e) {
+                    Some(value) => {
+                        let mut lexer = Lexer::new(value);
+                        lexer.continue_from(&self.lexers.last().expect("a lexer").0);
+                        self.lexers.push((lexer, value));
+                        continue;
+                    }
+                    None => Ok((s, Token::Word(word), e)),
+                },
+                token => token,
+            };
+            return Some(match self.expanding.first() {
+                // Everything in a value is where the alias' name was.
+                Some(&(_, s, e)) => match token {
+                    Ok((_, token, _)) => Ok((s, token, e)),
+                    Err(Error::UnrecognizedChar(_, c, _)) => Err(Error::UnrecognizedChar(s, c, e)),
+                    Err(Error::UnterminatedQuote(_, c, _)) => {
+                        Err(Error::UnterminatedQuote(s, c, e))
+                    }
+                },
+                None => token,
+            });
+        }
+    }
+}
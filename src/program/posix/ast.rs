@@ -1,5 +1,109 @@
 //! Abstract Syntax Tree for the POSIX language.
-use std::os::unix::io::RawFd;
+use std::{fmt, os::unix::io::RawFd};
+
+/// A region of the source text a node was parsed from.
+///
+/// Spans are kept as byte offsets, the line and column are only worked out
+/// from the text when they're needed (usually for a diagnostic).
+///
+/// ```
+/// use oursh::program::posix::ast::Span;
+///
+/// let text = "true\necho 1 > out";
+/// assert_eq!((2, 8), Span::new(12, 17).location(text));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// The smallest span covering both this and the `other` span.
+    pub fn to(self, other: Span) -> Self {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// The line and column (both starting from 1) of the start of this span
+    /// within `text`.
+    pub fn location(&self, text: &str) -> (usize, usize) {
+        let before = &text[..self.start.min(text.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = match before.rfind('\n') {
+            Some(i) => before[i + 1..].chars().count() + 1,
+            None => before.chars().count() + 1,
+        };
+        (line, column)
+    }
+
+    /// The position of the start of this span within `text`, which is read
+    /// from the file `name` starting on its `first_line`.
+    ///
+    /// ```
+    /// use oursh::program::posix::ast::Span;
+    ///
+    /// let text = "true\necho 1 > out";
+    /// let position = Span::new(12, 17).position("script.sh", text, 3);
+    /// assert_eq!("script.sh:4:8", position.to_string());
+    /// ```
+    pub fn position(&self, name: &str, text: &str, first_line: usize) -> Position {
+        let (line, column) = self.location(text);
+        Position {
+            file: name.into(),
+            line: first_line + line - 1,
+            column,
+        }
+    }
+}
+
+/// Where a node starts, by the file, line and column (both starting from 1),
+/// shown as `file:line:column`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    /// The name of the file, or whatever else the text was read from, like
+    /// `-c` or `stdin`.
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// A node of the syntax tree which knows where it came from.
+pub trait Spanned {
+    /// The region of the source this node was parsed from.
+    fn span(&self) -> Span;
+
+    /// The position this node starts at, in `text`, which is read from the
+    /// file `name` starting on its `first_line`.
+    ///
+    /// ```
+    /// use oursh::program::{
+    ///     parse_primary,
+    ///     posix::ast::{Command, Spanned},
+    ///     Program,
+    /// };
+    ///
+    /// let text = "true\n  echo 1 > out";
+    /// let program = parse_primary(text.as_bytes()).unwrap();
+    /// let position = program.commands()[1].position("-c", text, 1);
+    /// assert_eq!((2, 3), (position.line, position.column));
+    /// if let Command::Simple(_, _, redirects, _) = &program.commands()[1] {
+    ///     assert_eq!("-c:2:10", redirects[0].position("-c", text, 1).to_string());
+    /// }
+    /// ```
+    fn position(&self, name: &str, text: &str, first_line: usize) -> Position {
+        self.span().position(name, text, first_line)
+    }
+}
 
 /// A program is the result of parsing a sequence of commands.
 #[derive(Debug, Clone)]
@@ -16,14 +120,14 @@ pub enum Command {
     /// ```sh
     /// date --iso-8601
     /// ```
-    Simple(Vec<Assignment>, Vec<Word>, Vec<Redirect>),
+    Simple(Vec<Assignment>, Vec<Word>, Vec<Redirect>, Span),
 
     /// A full program embedded in a compound command.
     ///
     /// ```sh
     /// { ls ; }
     /// ```
-    Compound(Vec<Command>),
+    Compound(Vec<Command>, Span),
 
     /// Performs boolean negation to the status code of the inner
     /// command.
@@ -33,7 +137,18 @@ pub enum Command {
     /// ```sh
     /// ! grep 'password' data.txt
     /// ```
-    Not(Box<Command>),
+    Not(Box<Command>, Span),
+
+    /// Run a pipeline, then write how long it took and the CPU time it
+    /// used to standard error. With `-p` the times are written in the
+    /// POSIX format.
+    ///
+    /// ### Examples
+    ///
+    /// ```sh
+    /// time make | tail
+    /// ```
+    Time(Box<Command>, bool, Span),
 
     /// Perform the first command, conditionally running the next
     /// upon success.
@@ -43,7 +158,7 @@ pub enum Command {
     /// ```sh
     /// mkdir tmp && cd tmp
     /// ```
-    And(Box<Command>, Box<Command>),
+    And(Box<Command>, Box<Command>, Span),
 
     /// Perform the first command, conditionally running the next
     /// upon failure.
@@ -53,7 +168,7 @@ pub enum Command {
     /// ```sh
     /// kill $1 || kill -9 $1
     /// ```
-    Or(Box<Command>, Box<Command>),
+    Or(Box<Command>, Box<Command>, Span),
 
     /// Run the inner **program** in a sub-shell environment.
     ///
@@ -62,7 +177,7 @@ pub enum Command {
     /// ```sh
     /// DATE=(date)
     /// ```
-    Subshell(Box<Program>),
+    Subshell(Box<Program>, Span),
 
     /// Run a command's output through to the input of another.
     ///
@@ -71,7 +186,7 @@ pub enum Command {
     /// ```sh
     /// cat $1 | wc -l
     /// ```
-    Pipeline(Box<Command>, Box<Command>),
+    Pipeline(Box<Command>, Box<Command>, Span),
 
     /// Run a command in the background.
     ///
@@ -82,7 +197,7 @@ pub enum Command {
     ///   sleep 1; echo "ping";
     /// done &
     /// ```
-    Background(Box<Command>),
+    Background(Box<Command>, Span),
 
     /// Run a program through another parser/interpreter.
     ///
@@ -97,15 +212,46 @@ pub enum Command {
     /// This is **non-POSIX**
     ///
     /// TODO: How bad is it?
-    Lang(Interpreter, String),
+    Lang(Interpreter, String, Span),
 }
 
-/// A parsed word, already having gone through expansion.
-// TODO #8: How can we expand things like $1 or $? from the lexer?
-// TODO #8: This needs to handle escapes and all kinds of fun. We first
-//       need to decide on our custom Tokens and lexer.
+/// A single argument of a simple command.
 #[derive(Debug, Clone)]
-pub struct Word(pub String);
+pub enum Word {
+    /// A parsed word, which still needs expansion when the command is run.
+    // TODO #8: How can we expand things like $1 or $? from the lexer?
+    // TODO #8: This needs to handle escapes and all kinds of fun. We first
+    //       need to decide on our custom Tokens and lexer.
+    Literal(String, Span),
+
+    /// Process substitution, replaced by the path to a pipe the command can
+    /// read the program's output from.
+    ///
+    /// ### Examples
+    ///
+    /// ```sh
+    /// diff <(sort a) <(sort b)
+    /// ```
+    ///
+    /// ### Compatibility
+    ///
+    /// This is **non-POSIX**
+    ReadProcess(Box<Program>, Span),
+
+    /// Process substitution, replaced by the path to a pipe the command can
+    /// write the program's input to.
+    ///
+    /// ### Examples
+    ///
+    /// ```sh
+    /// echo foo | tee >(wc -c) | base64
+    /// ```
+    ///
+    /// ### Compatibility
+    ///
+    /// This is **non-POSIX**
+    WriteProcess(Box<Program>, Span),
+}
 
 #[derive(Debug, Clone)]
 pub enum Redirect {
@@ -114,6 +260,7 @@ pub enum Redirect {
     RW {
         n: RawFd,
         filename: String,
+        span: Span,
     },
     // Redirecting Input
     // [n]<word  (duplicate = false)
@@ -122,6 +269,7 @@ pub enum Redirect {
         n: RawFd,
         filename: String,
         duplicate: bool,
+        span: Span,
     },
     // Redirecting Output
     // [n]>word  // TODO: clobber flag needed.
@@ -134,6 +282,7 @@ pub enum Redirect {
         duplicate: bool,
         clobber: bool,
         append: bool,
+        span: Span,
     },
     // // Here-Document
     // // [n]<<word
@@ -155,6 +304,42 @@ impl Redirect {
             // Redirect::Here { ref mut n, .. } => n,
         }
     }
+
+    pub fn filename(&self) -> &str {
+        match self {
+            Redirect::RW { filename, .. } => filename,
+            Redirect::Read { filename, .. } => filename,
+            Redirect::Write { filename, .. } => filename,
+        }
+    }
+
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Redirect::RW { ref mut span, .. } => span,
+            Redirect::Read { ref mut span, .. } => span,
+            Redirect::Write { ref mut span, .. } => span,
+        }
+    }
+}
+
+impl Spanned for Redirect {
+    fn span(&self) -> Span {
+        match self {
+            Redirect::RW { span, .. } => *span,
+            Redirect::Read { span, .. } => *span,
+            Redirect::Write { span, .. } => *span,
+        }
+    }
+}
+
+impl Spanned for Word {
+    fn span(&self) -> Span {
+        match self {
+            Word::Literal(_, span) => *span,
+            Word::ReadProcess(_, span) => *span,
+            Word::WriteProcess(_, span) => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -163,10 +348,14 @@ pub struct Assignment(pub String, pub String);
 impl Command {
     pub fn push(mut self, command: &Command) -> Self {
         match self {
-            Command::Compound(ref mut c) => {
+            Command::Compound(ref mut c, ref mut span) => {
                 c.push(command.clone());
+                *span = span.to(command.span());
+            }
+            c => {
+                let span = c.span().to(command.span());
+                return Command::Compound(vec![c, command.clone()], span);
             }
-            c => return Command::Compound(vec![c, command.clone()]),
         }
 
         self
@@ -174,14 +363,152 @@ impl Command {
 
     pub fn insert(mut self, command: &Command) -> Self {
         match self {
-            Command::Compound(ref mut c) => {
+            Command::Compound(ref mut c, ref mut span) => {
                 c.insert(0, command.clone());
+                *span = span.to(command.span());
+            }
+            c => {
+                let span = c.span().to(command.span());
+                return Command::Compound(vec![command.clone(), c], span);
             }
-            c => return Command::Compound(vec![command.clone(), c]),
         }
 
         self
     }
+
+    /// The commands of a pipeline, in order. Any other command is a pipeline
+    /// of one.
+    pub fn pipeline(&self) -> Vec<&Command> {
+        match self {
+            Command::Pipeline(left, right, _) => {
+                let mut commands = left.pipeline();
+                commands.push(right);
+                commands
+            }
+            command => vec![command],
+        }
+    }
+}
+
+impl Spanned for Command {
+    fn span(&self) -> Span {
+        match self {
+            Command::Simple(_, _, _, span)
+            | Command::Compound(_, span)
+            | Command::Not(_, span)
+            | Command::Time(_, _, span)
+            | Command::And(_, _, span)
+            | Command::Or(_, _, span)
+            | Command::Subshell(_, span)
+            | Command::Pipeline(_, _, span)
+            | Command::Background(_, span)
+            | Command::Lang(_, _, span) => *span,
+        }
+    }
+}
+
+/// Commands are written out much like they'd be typed, for things like the
+/// text of a job. Quoting isn't kept, and `if` is written as the `&&` and
+/// `||` it's parsed into.
+///
+/// ```
+/// use oursh::program::{posix::ast::Program, Program as _};
+///
+/// let program = Program::parse(b"a=1 cat<in|wc -l 2>&1&&! true" as &[u8]).unwrap();
+/// assert_eq!("a=1 cat < in | wc -l 2>&1 && ! true", program.0[0].to_string());
+///
+/// let program = Program::parse(b"{ date;ls; }&" as &[u8]).unwrap();
+/// assert_eq!("{ date; ls; } &", program.0[0].to_string());
+/// ```
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Simple(assignments, words, redirects, _) => {
+                let parts: Vec<String> = assignments
+                    .iter()
+                    .map(|Assignment(name, value)| format!("{}={}", name, value))
+                    .chain(words.iter().map(Word::to_string))
+                    .chain(redirects.iter().map(Redirect::to_string))
+                    .collect();
+                write!(f, "{}", parts.join(" "))
+            }
+            Command::Compound(commands, _) => {
+                write!(f, "{{ ")?;
+                for command in commands {
+                    write!(f, "{}; ", command)?;
+                }
+                write!(f, "}}")
+            }
+            Command::Not(command, _) => write!(f, "! {}", command),
+            Command::Time(command, false, _) => write!(f, "time {}", command),
+            Command::Time(command, true, _) => write!(f, "time -p {}", command),
+            Command::And(left, right, _) => write!(f, "{} && {}", left, right),
+            Command::Or(left, right, _) => write!(f, "{} || {}", left, right),
+            Command::Subshell(program, _) => write!(f, "$({})", program),
+            Command::Pipeline(left, right, _) => write!(f, "{} | {}", left, right),
+            Command::Background(command, _) => write!(f, "{} &", command),
+            Command::Lang(interpreter, text, _) => match interpreter {
+                Interpreter::Primary | Interpreter::Alternate => write!(f, "{{#{}}}", text),
+                Interpreter::HashLang(language) => write!(f, "{{#{}{}}}", language, text),
+                Interpreter::Shebang(interpreter) => write!(f, "{{#!{}{}}}", interpreter, text),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let commands: Vec<String> = self.0.iter().map(Command::to_string).collect();
+        write!(f, "{}", commands.join("; "))
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Word::Literal(word, _) => write!(f, "{}", word),
+            Word::ReadProcess(program, _) => write!(f, "<({})", program),
+            Word::WriteProcess(program, _) => write!(f, ">({})", program),
+        }
+    }
+}
+
+/// The descriptor is only written when it isn't the default for the
+/// operator, so `2>&1` and `> out` are written as they usually are.
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (n, default, operator) = match self {
+            Redirect::RW { n, .. } => (*n, 0, "<>"),
+            Redirect::Read { n, duplicate, .. } => (*n, 0, if *duplicate { "<&" } else { "<" }),
+            Redirect::Write {
+                n,
+                duplicate,
+                clobber,
+                append,
+                ..
+            } => {
+                let operator = if *duplicate {
+                    ">&"
+                } else if *append {
+                    ">>"
+                } else if *clobber {
+                    ">|"
+                } else {
+                    ">"
+                };
+                (*n, 1, operator)
+            }
+        };
+        if n != default {
+            write!(f, "{}", n)?;
+        }
+        // Duplicated descriptors are written right after the operator.
+        if operator.ends_with('&') {
+            write!(f, "{}{}", operator, self.filename())
+        } else {
+            write!(f, "{} {}", operator, self.filename())
+        }
+    }
 }
 
 /// Either explicit or implicit declaration of the interperator for
@@ -225,7 +552,12 @@ mod tests {
     fn parse_program<'a>(text: &'a str) -> Result<Program, ParseError<usize, Token<'a>, Error>> {
         let lexer = Lexer::new(text);
         let parser = ProgramParser::new();
-        parser.parse(text, lexer)
+        let mut errors = vec![];
+        let program = parser.parse(text, &mut errors, lexer)?;
+        match errors.pop() {
+            Some(recovery) => Err(recovery.error),
+            None => Ok(program),
+        }
     }
 
     #[test]
@@ -241,7 +573,7 @@ mod tests {
     fn parse_command<'a>(text: &'a str) -> Result<Command, ParseError<usize, Token<'a>, Error>> {
         let lexer = Lexer::new(text);
         let parser = CommandParser::new();
-        parser.parse(text, lexer)
+        parser.parse(text, &mut vec![], lexer)
     }
 
     #[test]
@@ -251,6 +583,34 @@ mod tests {
         assert!(parse_command("ls -la").is_ok());
     }
 
+    #[test]
+    fn spans() {
+        let command = parse_command("echo hi 2>err && ! true").unwrap();
+        assert_eq!(Span::new(0, 23), command.span());
+        if let Command::And(box Command::Simple(_, words, redirects, span), not, _) = command {
+            assert_eq!(Span::new(0, 13), span);
+            assert_eq!(Span::new(5, 7), words[1].span());
+            assert_eq!(Span::new(8, 13), redirects[0].span());
+            assert_eq!(Span::new(17, 23), not.span());
+        } else {
+            panic!("expected an and of a simple command");
+        }
+
+        let command = parse_command("{ ls; date; }").unwrap();
+        assert_eq!(Span::new(0, 13), command.span());
+    }
+
+    #[test]
+    #[cfg(feature = "process-substitution")]
+    fn process_substitution() {
+        let command = parse_command("diff <(ls) >(wc; date)").unwrap();
+        assert_matches!(&command, Command::Simple(_, w, _, _) if w.len() == 3);
+        if let Command::Simple(_, words, _, _) = command {
+            assert_matches!(&words[1], Word::ReadProcess(p, _) if p.0.len() == 1);
+            assert_matches!(&words[2], Word::WriteProcess(p, _) if p.0.len() == 2);
+        }
+    }
+
     #[test]
     fn compound_command() {
         assert!(parse_command("{ls}").is_err());
@@ -258,39 +618,39 @@ mod tests {
 
         let text = "{ls;}";
         let command = parse_command(text).unwrap();
-        assert_matches!(&command, Command::Compound(c) if c.len() == 1);
+        assert_matches!(&command, Command::Compound(c, _) if c.len() == 1);
 
         let text = "{ls; date;}";
         let command = parse_command(text).unwrap();
-        assert_matches!(&command, Command::Compound(c) if c.len() == 2);
+        assert_matches!(&command, Command::Compound(c, _) if c.len() == 2);
 
         let text = "{git s; ls -la; true;}";
         let command = parse_command(text).unwrap();
-        assert_matches!(&command, Command::Compound(c) if c.len() == 3);
+        assert_matches!(&command, Command::Compound(c, _) if c.len() == 3);
     }
 
     #[test]
     fn not_command() {
         let command = parse_command("! true").unwrap();
-        assert_matches!(command, Command::Not(_));
+        assert_matches!(command, Command::Not(..));
         let command = parse_command("! true || false").unwrap();
-        assert_matches!(command, Command::Or(box Command::Not(_), _));
+        assert_matches!(command, Command::Or(box Command::Not(..), ..));
     }
 
     #[test]
     fn and_command() {
         let command = parse_command("true && false").unwrap();
-        assert_matches!(command, Command::And(_, _));
+        assert_matches!(command, Command::And(..));
         let command = parse_command("true || false && true").unwrap();
-        assert_matches!(command, Command::And(_, _));
+        assert_matches!(command, Command::And(..));
     }
 
     #[test]
     fn or_command() {
         let command = parse_command("true || false").unwrap();
-        assert_matches!(command, Command::Or(_, _));
+        assert_matches!(command, Command::Or(..));
         let command = parse_command("true && false || true").unwrap();
-        assert_matches!(command, Command::Or(_, _));
+        assert_matches!(command, Command::Or(..));
     }
 
     #[test]
@@ -299,15 +659,15 @@ mod tests {
         assert!(parse_command("$()").is_ok());
 
         let command = parse_command("$(ls)").unwrap();
-        assert_matches!(command, Command::Subshell(_));
+        assert_matches!(command, Command::Subshell(..));
 
         let command = parse_command("$(date;)").unwrap();
-        assert_matches!(command, Command::Subshell(_));
+        assert_matches!(command, Command::Subshell(..));
 
         let command = parse_command("$(date; ls)").unwrap();
-        assert_matches!(command, Command::Subshell(_));
+        assert_matches!(command, Command::Subshell(..));
 
         let command = parse_command("$(date; ls -la;)").unwrap();
-        assert_matches!(command, Command::Subshell(_));
+        assert_matches!(command, Command::Subshell(..));
     }
 }
@@ -0,0 +1,46 @@
+use crate::{
+    process::ExitStatus,
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use std::ffi::CString;
+
+/// Alias builtin, defining and showing aliases.
+///
+/// Aliases are shown as `name='value'`, which `alias` could take to define
+/// them again.
+pub struct Alias;
+
+impl Builtin for Alias {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        if argv.len() == 1 {
+            for (name, value) in runtime.aliases.iter() {
+                println!("{}", definition(name, value));
+            }
+            return Ok(ExitStatus::SUCCESS);
+        }
+
+        let mut status = ExitStatus::SUCCESS;
+        for arg in argv[1..].iter() {
+            let arg = arg.to_string_lossy();
+            match arg.split_once('=') {
+                Some((name, value)) => {
+                    runtime.aliases.insert(name.into(), value.into());
+                }
+                None => match runtime.aliases.get(&*arg) {
+                    Some(value) => println!("{}", definition(&arg, value)),
+                    None => {
+                        eprintln!("oursh: alias: {}: not found", arg);
+                        status = ExitStatus::Exited(1);
+                    }
+                },
+            }
+        }
+        Ok(status)
+    }
+}
+
+// The alias as it would be defined, single quoting the value.
+fn definition(name: &str, value: &str) -> String {
+    format!("{}='{}'", name, value.replace('\'', "'\\''"))
+}
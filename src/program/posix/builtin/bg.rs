@@ -0,0 +1,51 @@
+use crate::{
+    process::{session, ExitStatus},
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use nix::sys::signal::Signal;
+use std::ffi::CString;
+
+/// Background builtin, continuing stopped jobs without waiting for them.
+///
+/// Without a job spec the current job is used.
+pub struct Bg;
+
+impl Builtin for Bg {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        if !session::enabled() {
+            eprintln!("oursh: bg: no job control");
+            return Ok(ExitStatus::Exited(1));
+        }
+
+        let mut specs: Vec<_> = argv[1..].iter().map(|a| a.to_string_lossy()).collect();
+        if specs.is_empty() {
+            specs.push("%".into());
+        }
+
+        let mut status = ExitStatus::SUCCESS;
+        for spec in specs {
+            let id = match runtime.jobs.find(spec.strip_prefix('%').unwrap_or(&spec)) {
+                Ok(id) => id,
+                Err(e) => {
+                    eprintln!("oursh: bg: {}: {}", spec, e);
+                    status = ExitStatus::Exited(1);
+                    continue;
+                }
+            };
+            let job = &mut runtime.jobs[id];
+            match job.group().signal(Some(Signal::SIGCONT)) {
+                Ok(()) => {
+                    job.continued();
+                    let jobs = &runtime.jobs;
+                    println!("[{}]{}\t{} &", id, jobs.marker(id), jobs[id].text());
+                }
+                Err(e) => {
+                    eprintln!("oursh: bg: {}: {}", spec, e.desc());
+                    status = ExitStatus::Exited(1);
+                }
+            }
+        }
+        Ok(status)
+    }
+}
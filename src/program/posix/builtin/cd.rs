@@ -1,11 +1,9 @@
 use crate::{
+    process::ExitStatus,
     program::posix::builtin::Builtin,
-    program::{Error, Result, Runtime},
-};
-use nix::{
-    sys::wait::WaitStatus,
-    unistd::{chdir, Pid},
+    program::{Result, Runtime},
 };
+use nix::unistd::chdir;
 use std::{
     env::{self, set_var},
     ffi::CString,
@@ -15,7 +13,7 @@ use std::{
 pub struct Cd;
 
 impl Builtin for Cd {
-    fn run(self, argv: Vec<CString>, _: &mut Runtime) -> Result<WaitStatus> {
+    fn run(self, argv: Vec<CString>, _: &mut Runtime) -> Result<ExitStatus> {
         match argv.len() {
             0 => {
                 panic!("command name not passed in argv[0]");
@@ -23,29 +21,31 @@ impl Builtin for Cd {
             1 => {
                 let home = match env::var("HOME") {
                     Ok(path) => path,
-                    Err(_) => return Err(Error::Runtime),
+                    Err(_) => {
+                        eprintln!("oursh: cd: HOME not set");
+                        return Ok(ExitStatus::Exited(1));
+                    }
                 };
-                let dst = home.as_str();
-                chdir(dst)
-                    .map(|_| {
-                        set_var("PWD", &dst);
-                        WaitStatus::Exited(Pid::this(), 0)
-                    })
-                    .map_err(|_| Error::Runtime)
-            }
-            2 => {
-                let dst = argv[1].to_string_lossy();
-                chdir(dst.as_ref())
-                    .map(|_| {
-                        set_var("PWD", dst.as_ref());
-                        WaitStatus::Exited(Pid::this(), 0)
-                    })
-                    .map_err(|_| Error::Runtime)
+                Ok(change(&home))
             }
+            2 => Ok(change(&argv[1].to_string_lossy())),
             _ => {
                 eprintln!("too many arguments");
-                Ok(WaitStatus::Exited(Pid::this(), 1))
+                Ok(ExitStatus::Exited(1))
             }
         }
     }
 }
+
+fn change(dst: &str) -> ExitStatus {
+    match chdir(dst) {
+        Ok(()) => {
+            set_var("PWD", dst);
+            ExitStatus::Exited(0)
+        }
+        Err(e) => {
+            eprintln!("oursh: cd: {}: {}", dst, e.desc());
+            ExitStatus::Exited(1)
+        }
+    }
+}
@@ -1,20 +1,21 @@
 use crate::{
+    process::ExitStatus,
     program::posix::builtin::Builtin,
     program::{parse_and_run, Result, Runtime},
 };
-use nix::sys::wait::WaitStatus;
 use std::ffi::CString;
 
 /// Command builtin, I have no idea why you'd want this honestly.
 pub struct Command;
 
 impl Builtin for Command {
-    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<WaitStatus> {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
         let text = argv[1..]
             .iter()
             .map(|c| c.to_str().unwrap())
             .collect::<Vec<_>>()
             .join(" ");
-        parse_and_run(&text, runtime)
+        // Any error has been reported, leaving only its status.
+        Ok(parse_and_run("command", &text, runtime).unwrap_or_else(|e| e.status()))
     }
 }
@@ -0,0 +1,92 @@
+use crate::{
+    process::{jobs::State, ExitStatus},
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use nix::unistd::Pid;
+use std::ffi::CString;
+
+/// Disown builtin, removing jobs from the table so the shell leaves them
+/// be, even when it hangs up.
+///
+/// Jobs are given by `%` job specs or the process ID of any of their
+/// processes, and default to the current job. `-a` disowns every job, and
+/// `-r` only those which are running. With `-h` the jobs stay in the table,
+/// they just aren't sent `SIGHUP`.
+pub struct Disown;
+
+impl Builtin for Disown {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let args: Vec<_> = argv[1..].iter().map(|a| a.to_string_lossy()).collect();
+        let mut args = &args[..];
+
+        let (mut keep, mut all, mut running) = (false, false, false);
+        while let Some(option) = args.first() {
+            if option == "--" {
+                args = &args[1..];
+                break;
+            }
+            if !option.starts_with('-') || option == "-" {
+                break;
+            }
+            for letter in option[1..].chars() {
+                match letter {
+                    'h' => keep = true,
+                    'a' => all = true,
+                    'r' => running = true,
+                    _ => {
+                        eprintln!("oursh: disown: -{}: invalid option", letter);
+                        eprintln!("oursh: disown: usage: disown [-h] [-ar] [job ...]");
+                        return Ok(ExitStatus::Exited(2));
+                    }
+                }
+            }
+            args = &args[1..];
+        }
+
+        let mut status = ExitStatus::SUCCESS;
+        let mut ids: Vec<usize> = vec![];
+        if args.is_empty() && (all || running) {
+            ids = runtime.jobs.iter().map(|job| job.id()).collect();
+        } else if args.is_empty() {
+            match runtime.jobs.current() {
+                Some(id) => ids.push(id),
+                None => {
+                    eprintln!("oursh: disown: current: no such job");
+                    status = ExitStatus::Exited(1);
+                }
+            }
+        }
+        for arg in args {
+            let found = match arg.parse() {
+                Ok(pid) if !arg.starts_with('%') => runtime
+                    .jobs
+                    .containing(Pid::from_raw(pid))
+                    .ok_or("no such job"),
+                _ => runtime.jobs.find(arg.strip_prefix('%').unwrap_or(arg)),
+            };
+            match found {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    eprintln!("oursh: disown: {}: {}", arg, e);
+                    status = ExitStatus::Exited(1);
+                }
+            }
+        }
+
+        for id in ids {
+            if running && runtime.jobs[id].state() != State::Running {
+                continue;
+            }
+            if keep {
+                runtime.jobs[id].ignore_hangup();
+            } else if let Some(job) = runtime.jobs.remove(id) {
+                // Still reaped once they're done, just never reported.
+                for process in job.into_group().into_processes() {
+                    runtime.jobs.add_helper(process);
+                }
+            }
+        }
+        Ok(status)
+    }
+}
@@ -1,8 +1,9 @@
 use crate::{
+    process::ExitStatus,
     program::posix::builtin::Builtin,
     program::{parse_and_run, Result, Runtime},
 };
-use nix::{sys::wait::WaitStatus, unistd::Pid};
+use nix::errno::Errno;
 use std::{ffi::CString, fs::File, io::Read};
 
 /// Execute commands from `file` in the current environment
@@ -17,24 +18,27 @@ use std::{ffi::CString, fs::File, io::Read};
 pub struct Dot;
 
 impl Builtin for Dot {
-    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<WaitStatus> {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
         match argv.len() {
             0 => unreachable!(),
             1 => {
                 eprintln!("filename argument required");
-                Ok(WaitStatus::Exited(Pid::this(), 2))
+                Ok(ExitStatus::Exited(2))
             }
             2 => {
-                let path = argv[1].to_str().unwrap();
-                if let Ok(mut file) = File::open(&path) {
-                    let mut contents = String::new();
-                    if file.read_to_string(&mut contents).is_ok() {
-                        parse_and_run(&contents, runtime)
-                    } else {
-                        Ok(WaitStatus::Exited(Pid::this(), 1))
+                let path = argv[1].to_string_lossy();
+                let mut contents = String::new();
+                match File::open(path.as_ref()).and_then(|mut f| f.read_to_string(&mut contents)) {
+                    // Any error has been reported, leaving only its status.
+                    Ok(_) => {
+                        let result = parse_and_run(&path, &contents, runtime);
+                        Ok(result.unwrap_or_else(|e| e.status()))
+                    }
+                    Err(e) => {
+                        let errno = Errno::from_i32(e.raw_os_error().unwrap_or_default());
+                        eprintln!("oursh: .: {}: {}", path, errno.desc());
+                        Ok(ExitStatus::Exited(1))
                     }
-                } else {
-                    Ok(WaitStatus::Exited(Pid::this(), 1))
                 }
             }
             _ => unreachable!(),
@@ -0,0 +1,86 @@
+use crate::{
+    process::{ExitStatus, Process, IO},
+    program::posix::{builtin::Builtin, handle},
+    program::{run_exit_trap, Result, Runtime},
+};
+use nix::errno::Errno;
+use std::{ffi::CString, process};
+
+/// Exec builtin, replaces the shell with a command or makes redirections
+/// permanent.
+///
+/// ```sh
+/// # Send the rest of the script's output to a log.
+/// exec >logfile 2>&1
+/// # Open a descriptor for later commands to use.
+/// exec 3<file
+/// # Become another program.
+/// exec sh
+/// ```
+///
+/// A command which can't be found is handed to `command_not_found_handle`,
+/// like any other.
+///
+/// When the command can't be run, the redirections are undone, and a shell
+/// which isn't interactive exits with 127 if the command wasn't found, or
+/// 126 otherwise.
+pub struct Exec;
+
+impl Builtin for Exec {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        match argv.len() {
+            0 => unreachable!(),
+            1 => {
+                // Install the command's redirections onto the shell's own
+                // descriptors, so everything after this sees them.
+                if let Err(e) = runtime.io.dup() {
+                    eprintln!("oursh: exec: {}", e.desc());
+                    return Ok(ExitStatus::Exited(1));
+                }
+                runtime.io = IO::default();
+                Ok(ExitStatus::Exited(0))
+            }
+            _ => {
+                #[cfg(feature = "history")]
+                runtime.history.save_or_report();
+
+                // Keep the shell's own descriptors, to put back if the
+                // command can't be run.
+                let saved = match runtime.io.save() {
+                    Ok(saved) => saved,
+                    Err(e) => {
+                        eprintln!("oursh: exec: {}", e.desc());
+                        return Ok(ExitStatus::Exited(1));
+                    }
+                };
+                if let Err(e) = runtime.io.dup() {
+                    eprintln!("oursh: exec: {}", e.desc());
+                    let _ = saved.restore();
+                    return Ok(ExitStatus::Exited(1));
+                }
+                let process = Process::new(handle(argv[1..].to_vec()));
+                let name = argv[1].to_string_lossy();
+                let code = match process.exec() {
+                    Err(Errno::ENOENT) => {
+                        eprintln!("oursh: exec: {}: not found", name);
+                        127
+                    }
+                    Err(e) => {
+                        eprintln!("oursh: exec: {}: {}", name, e.desc());
+                        126
+                    }
+                    Ok(()) => unreachable!(),
+                };
+                if let Err(e) = saved.restore() {
+                    eprintln!("oursh: exec: {}", e.desc());
+                }
+                if !runtime.options.interactive {
+                    *runtime.status = ExitStatus::Exited(code);
+                    run_exit_trap(runtime);
+                    process::exit(code);
+                }
+                Ok(ExitStatus::Exited(code))
+            }
+        }
+    }
+}
@@ -1,37 +1,50 @@
 use crate::{
+    process::ExitStatus,
     program::posix::builtin::Builtin,
-    program::{Result, Runtime},
+    program::{may_exit, run_exit_trap, Result, Runtime},
 };
-use nix::{sys::wait::WaitStatus, unistd::Pid};
 use std::{ffi::CString, process};
 
 /// Exit builtin, alternative to ctrl-d.
+///
+/// With stopped jobs the first attempt only warns about them, see
+/// [`may_exit`].
 pub struct Exit;
 
 impl Builtin for Exit {
-    #[allow(unused_variables)]
-    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<WaitStatus> {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        if !may_exit(runtime) {
+            return Ok(ExitStatus::Exited(1));
+        }
+
         #[cfg(feature = "history")]
         if argv.len() == 1 || argv.len() == 2 {
-            runtime.history.save().unwrap();
+            runtime.history.save_or_report();
         }
 
         match argv.len() {
             0 => {
                 panic!("command name not passed in argv[0]");
             }
-            1 => process::exit(0),
+            1 => exit(runtime.status.code(), runtime),
             2 => {
                 if let Ok(n) = str::parse(argv[1].to_str().unwrap()) {
-                    process::exit(n)
+                    exit(n, runtime)
                 } else {
-                    process::exit(2)
+                    exit(2, runtime)
                 }
             }
             _ => {
                 eprintln!("too many arguments");
-                Ok(WaitStatus::Exited(Pid::this(), 1))
+                Ok(ExitStatus::Exited(1))
             }
         }
     }
 }
+
+// Exit the shell with the given code, once the `EXIT` trap has run.
+fn exit(code: i32, runtime: &mut Runtime) -> ! {
+    *runtime.status = ExitStatus::Exited(code);
+    run_exit_trap(runtime);
+    process::exit(code)
+}
@@ -1,15 +1,15 @@
 use crate::{
+    process::ExitStatus,
     program::posix::builtin::Builtin,
     program::{Result, Runtime},
 };
-use nix::{sys::wait::WaitStatus, unistd::Pid};
 use std::{env, ffi::CString};
 
 /// Export builtin, used to set global variables.
 pub struct Export;
 
 impl Builtin for Export {
-    fn run(self, argv: Vec<CString>, _: &mut Runtime) -> Result<WaitStatus> {
+    fn run(self, argv: Vec<CString>, _: &mut Runtime) -> Result<ExitStatus> {
         match argv.len() {
             0 => unreachable!(),
             1 => {
@@ -23,7 +23,7 @@ impl Builtin for Export {
                         env::set_var(key, value);
                     }
                 }
-                Ok(WaitStatus::Exited(Pid::this(), 0))
+                Ok(ExitStatus::Exited(0))
             }
         }
     }
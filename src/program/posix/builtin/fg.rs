@@ -0,0 +1,42 @@
+use crate::{
+    process::{session, ExitStatus},
+    program::posix::{builtin::Builtin, foreground},
+    program::{Result, Runtime},
+};
+use nix::sys::signal::Signal;
+use std::ffi::CString;
+
+/// Foreground builtin, continuing a job with the terminal handed to it, and
+/// waiting for it.
+///
+/// Without a job spec the current job is used.
+pub struct Fg;
+
+impl Builtin for Fg {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        if !session::enabled() {
+            eprintln!("oursh: fg: no job control");
+            return Ok(ExitStatus::Exited(1));
+        }
+
+        let spec = argv.get(1).map(|a| a.to_string_lossy()).unwrap_or_default();
+        let id = match runtime.jobs.find(spec.strip_prefix('%').unwrap_or(&spec)) {
+            Ok(id) => id,
+            Err(e) => {
+                eprintln!("oursh: fg: {}: {}", spec, e);
+                return Ok(ExitStatus::Exited(1));
+            }
+        };
+        let job = &mut runtime.jobs[id];
+
+        println!("{}", job.text());
+        session::foreground(job.group().pgid());
+        if let Err(e) = job.group().signal(Some(Signal::SIGCONT)) {
+            session::reclaim();
+            eprintln!("oursh: fg: {}: {}", spec, e.desc());
+            return Ok(ExitStatus::Exited(1));
+        }
+        job.continued();
+        foreground(id, runtime)
+    }
+}
@@ -1,23 +1,89 @@
 use crate::{
+    process::{jobs::State, ExitStatus},
     program::posix::builtin::Builtin,
     program::{Result, Runtime},
 };
-use nix::{sys::wait::WaitStatus, unistd::Pid};
 use std::ffi::CString;
 
-/// Background job information.
+/// Jobs builtin, listing the jobs with their state.
+///
+/// `-l` lists each process of the jobs with its process ID too, and `-p`
+/// only the jobs' process group IDs. Without job specs every job is listed.
+/// Jobs which are done are forgotten once they've been listed.
 pub struct Jobs;
 
 impl Builtin for Jobs {
-    fn run(self, _: Vec<CString>, runtime: &mut Runtime) -> Result<WaitStatus> {
-        for (id, job) in runtime.jobs.borrow().iter() {
-            println!(
-                "[{}]\t{}\t\t{}",
-                id,
-                job.leader().pid(),
-                job.leader().body()
-            );
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let args: Vec<_> = argv[1..].iter().map(|a| a.to_string_lossy()).collect();
+        let mut args = &args[..];
+
+        let (mut long, mut pids) = (false, false);
+        while let Some(option) = args.first() {
+            match option.as_ref() {
+                "-l" => long = true,
+                "-p" => pids = true,
+                "--" => {
+                    args = &args[1..];
+                    break;
+                }
+                option if option.starts_with('-') && option != "-" => {
+                    eprintln!("oursh: jobs: {}: invalid option", option);
+                    eprintln!("oursh: jobs: usage: jobs [-l|-p] [job ...]");
+                    return Ok(ExitStatus::Exited(2));
+                }
+                _ => break,
+            }
+            args = &args[1..];
+        }
+
+        runtime.jobs.poll();
+        let mut status = ExitStatus::SUCCESS;
+        let ids: Vec<usize> = if args.is_empty() {
+            runtime.jobs.iter().map(|job| job.id()).collect()
+        } else {
+            args.iter()
+                .filter_map(|spec| {
+                    match runtime.jobs.find(spec.strip_prefix('%').unwrap_or(spec)) {
+                        Ok(id) => Some(id),
+                        Err(e) => {
+                            eprintln!("oursh: jobs: {}: {}", spec, e);
+                            status = ExitStatus::Exited(1);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        };
+
+        for id in ids {
+            let jobs = &runtime.jobs;
+            let job = &jobs[id];
+            if pids {
+                println!("{}", job.group().pgid());
+            } else if long {
+                let marker = format!("[{}]{}", id, jobs.marker(id));
+                for (i, (process, state)) in job.processes().enumerate() {
+                    let (marker, pipe) = if i == 0 { (&*marker, "") } else { ("", "| ") };
+                    println!(
+                        "{}\t{}\t{}\t{}{}",
+                        marker,
+                        process.pid(),
+                        state,
+                        pipe,
+                        process.body()
+                    );
+                }
+            } else if job.state() == State::Running {
+                println!("{} &", jobs.report(id));
+            } else {
+                println!("{}", jobs.report(id));
+            }
+            if let State::Done(_) = job.state() {
+                runtime.jobs.forget(id);
+            } else {
+                runtime.jobs.reported(id);
+            }
         }
-        Ok(WaitStatus::Exited(Pid::this(), 0))
+        Ok(status)
     }
 }
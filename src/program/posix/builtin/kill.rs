@@ -0,0 +1,137 @@
+use crate::{
+    process::{signal::Condition, ExitStatus},
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use std::{convert::TryFrom, ffi::CString};
+
+/// Kill builtin, sending a signal to processes, or to whole jobs given by
+/// `%` job specs.
+///
+/// The signal is given with `-s NAME`, `-NAME` or `-NUMBER`, and defaults to
+/// `SIGTERM`. `kill -l` lists the names of the signals, or converts between
+/// names and numbers.
+pub struct Kill;
+
+impl Builtin for Kill {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let args: Vec<_> = argv[1..].iter().map(|a| a.to_string_lossy()).collect();
+        let mut args = &args[..];
+
+        let mut signal = Some(Signal::SIGTERM);
+        match args.first().map(|a| a.as_ref()) {
+            Some("-l") => return Ok(list(&args[1..])),
+            Some("-s") if args.len() > 1 => {
+                signal = match parse(&args[1]) {
+                    Some(signal) => signal,
+                    None => return Ok(invalid(&args[1])),
+                };
+                args = &args[2..];
+            }
+            Some(option) if option.starts_with('-') && option != "-" && option != "--" => {
+                signal = match parse(&option[1..]) {
+                    Some(signal) => signal,
+                    None => return Ok(invalid(&option[1..])),
+                };
+                args = &args[1..];
+            }
+            _ => {}
+        }
+        if args.first().is_some_and(|a| a == "--") {
+            args = &args[1..];
+        }
+        if args.is_empty() {
+            eprintln!(
+                "oursh: kill: usage: kill [-s signal | -signal] pid | %job ... or kill -l [signal]"
+            );
+            return Ok(ExitStatus::Exited(2));
+        }
+
+        let mut status = ExitStatus::SUCCESS;
+        for target in args {
+            let result = if let Some(spec) = target.strip_prefix('%') {
+                let id = match runtime.jobs.find(spec) {
+                    Ok(id) => id,
+                    Err(e) => {
+                        eprintln!("oursh: kill: {}: {}", target, e);
+                        status = ExitStatus::Exited(1);
+                        continue;
+                    }
+                };
+                let group = runtime.jobs[id].group();
+                // A stopped job needs to be continued to be terminated.
+                group.signal(signal).and_then(|_| match signal {
+                    Some(Signal::SIGTERM) | Some(Signal::SIGHUP) => {
+                        group.signal(Some(Signal::SIGCONT))
+                    }
+                    _ => Ok(()),
+                })
+            } else {
+                match target.parse() {
+                    Ok(pid) => kill(Pid::from_raw(pid), signal),
+                    Err(_) => {
+                        eprintln!(
+                            "oursh: kill: {}: arguments must be process or job IDs",
+                            target
+                        );
+                        status = ExitStatus::Exited(1);
+                        continue;
+                    }
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("oursh: kill: {}: {}", target, e.desc());
+                status = ExitStatus::Exited(1);
+            }
+        }
+        Ok(status)
+    }
+}
+
+// The signal named or numbered, `None` being the null signal `0` which only
+// checks that the processes exist.
+fn parse(name: &str) -> Option<Option<Signal>> {
+    match name.parse() {
+        Ok(Condition::Signal(signal)) => Some(Some(signal)),
+        Ok(Condition::Exit) => Some(None),
+        Err(()) => None,
+    }
+}
+
+fn invalid(name: &str) -> ExitStatus {
+    eprintln!("oursh: kill: {}: invalid signal specification", name);
+    ExitStatus::Exited(1)
+}
+
+// List every signal's name, or convert each of the given signals from a
+// number to a name or back. The status of a command killed by a signal
+// converts to the signal's name too.
+fn list(args: &[impl AsRef<str>]) -> ExitStatus {
+    if args.is_empty() {
+        let names: Vec<_> = Signal::iterator()
+            .map(|s| Condition::Signal(s).to_string())
+            .collect();
+        println!("{}", names.join(" "));
+        return ExitStatus::SUCCESS;
+    }
+
+    let mut status = ExitStatus::SUCCESS;
+    for arg in args {
+        let arg = arg.as_ref();
+        match arg.parse::<i32>() {
+            Ok(n) => match Signal::try_from(if n > 128 { n - 128 } else { n }) {
+                Ok(signal) => println!("{}", Condition::Signal(signal)),
+                Err(_) => status = invalid(arg),
+            },
+            Err(_) => match parse(arg) {
+                Some(Some(signal)) => println!("{}", signal as i32),
+                _ => status = invalid(arg),
+            },
+        }
+    }
+    status
+}
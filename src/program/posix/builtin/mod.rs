@@ -3,8 +3,10 @@
 //!
 //! These commands take precedence over any executables with the same name
 //! in the `$PATH`.
-use crate::program::{Result, Runtime};
-use nix::sys::wait::WaitStatus;
+use crate::{
+    process::ExitStatus,
+    program::{Result, Runtime},
+};
 use std::ffi::CString;
 
 /// A builtin is a custom shell command, often changing the state of the
@@ -12,22 +14,42 @@ use std::ffi::CString;
 pub trait Builtin {
     /// Execute the shell builtin command, returning a retult of the
     /// completion.
-    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<WaitStatus>;
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus>;
 }
 
+mod alias;
+pub use self::alias::Alias;
+mod bg;
+pub use self::bg::Bg;
 mod cd;
 pub use self::cd::Cd;
 mod command;
 pub use self::command::Command;
+mod disown;
+pub use self::disown::Disown;
 mod dot;
 pub use self::dot::Dot;
+mod exec;
+pub use self::exec::Exec;
 mod exit;
 pub use self::exit::Exit;
 mod export;
 pub use self::export::Export;
+mod fg;
+pub use self::fg::Fg;
 mod jobs;
 pub use self::jobs::Jobs;
+mod kill;
+pub use self::kill::Kill;
 mod r#return;
 pub use self::r#return::Return;
+mod set;
+pub use self::set::Set;
+mod times;
+pub use self::times::Times;
+mod trap;
+pub use self::trap::Trap;
+mod unalias;
+pub use self::unalias::Unalias;
 mod wait;
 pub use self::wait::Wait;
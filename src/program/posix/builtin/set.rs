@@ -0,0 +1,106 @@
+use crate::{
+    process::ExitStatus,
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use std::{env, ffi::CString};
+
+/// Set builtin, setting the shell's options, or listing its variables.
+///
+/// Options are turned on with `-` and off with `+`, either by letter or by
+/// name with `-o name`. Without a name `-o` lists the options, and `+o`
+/// lists them as the commands which would set them again.
+///
+/// - `-b`, `-o notify` reports jobs as soon as they stop or finish
+/// - `-o timing` records the times of finished jobs, for `times`
+pub struct Set;
+
+// The options there are, by name and letter, if they have one.
+const OPTIONS: [(Option<char>, &str); 2] = [(Some('b'), "notify"), (None, "timing")];
+
+impl Builtin for Set {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        if argv.len() == 1 {
+            let mut variables: Vec<_> = env::vars().collect();
+            variables.sort();
+            for (name, value) in variables {
+                println!("{}='{}'", name, value.replace('\'', "'\\''"));
+            }
+            return Ok(ExitStatus::SUCCESS);
+        }
+
+        let args: Vec<_> = argv[1..].iter().map(|a| a.to_string_lossy()).collect();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let on = match arg.chars().next() {
+                Some('-') if arg != "-" && arg != "--" => true,
+                Some('+') if arg != "+" => false,
+                _ => {
+                    eprintln!("oursh: set: {}: positional parameters can't be set", arg);
+                    return Ok(ExitStatus::Exited(1));
+                }
+            };
+
+            if &arg[1..] == "o" {
+                let name = match args.next() {
+                    Some(name) => name,
+                    None => {
+                        list(on, runtime);
+                        continue;
+                    }
+                };
+                match OPTIONS.iter().find(|(_, n)| n == name) {
+                    Some((_, name)) => set_option(name, on, runtime),
+                    None => {
+                        eprintln!("oursh: set: {}: invalid option name", name);
+                        return Ok(ExitStatus::Exited(2));
+                    }
+                }
+                continue;
+            }
+
+            for letter in arg[1..].chars() {
+                match OPTIONS.iter().find(|(l, _)| *l == Some(letter)) {
+                    Some((_, name)) => set_option(name, on, runtime),
+                    None => {
+                        eprintln!("oursh: set: {}{}: invalid option", &arg[..1], letter);
+                        eprintln!("oursh: set: usage: set [-+b] [-+o option]");
+                        return Ok(ExitStatus::Exited(2));
+                    }
+                }
+            }
+        }
+        Ok(ExitStatus::SUCCESS)
+    }
+}
+
+// Whether the option with the given name is on.
+fn option(name: &str, runtime: &Runtime) -> bool {
+    match name {
+        "notify" => runtime.options.notify,
+        "timing" => runtime.jobs.is_timing(),
+        _ => unreachable!("unknown option"),
+    }
+}
+
+// Turn the option with the given name on or off.
+fn set_option(name: &str, on: bool, runtime: &mut Runtime) {
+    match name {
+        "notify" => runtime.options.notify = on,
+        "timing" => runtime.jobs.set_timing(on),
+        _ => unreachable!("unknown option"),
+    }
+}
+
+// List the options and whether they're on, or unless `readable`, as the
+// `set` commands which would turn them on or off again.
+fn list(readable: bool, runtime: &Runtime) {
+    for (_, name) in OPTIONS {
+        let on = option(name, runtime);
+        if readable {
+            println!("{:<15}\t{}", name, if on { "on" } else { "off" });
+        } else {
+            println!("set {}o {}", if on { '-' } else { '+' }, name);
+        }
+    }
+}
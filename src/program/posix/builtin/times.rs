@@ -0,0 +1,34 @@
+use crate::{
+    process::{
+        usage::{minutes, Usage},
+        ExitStatus,
+    },
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use std::ffi::CString;
+
+/// Times builtin, writing the user and system time used by the shell, then
+/// by the commands it has run.
+///
+/// With `set -o timing` the times of each finished job follow, the real
+/// time first, along with its command.
+pub struct Times;
+
+impl Builtin for Times {
+    fn run(self, _: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        for usage in [Usage::shell(), Usage::children()] {
+            println!("{} {}", minutes(usage.user), minutes(usage.system));
+        }
+        for timing in runtime.jobs.timings() {
+            println!(
+                "{} {} {}\t{}",
+                minutes(timing.real),
+                minutes(timing.usage.user),
+                minutes(timing.usage.system),
+                timing.text
+            );
+        }
+        Ok(ExitStatus::SUCCESS)
+    }
+}
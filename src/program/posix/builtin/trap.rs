@@ -0,0 +1,74 @@
+use crate::{
+    process::{
+        signal::{self, Condition},
+        ExitStatus,
+    },
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use std::ffi::CString;
+
+/// Trap builtin, setting the program to run when the shell gets a signal or
+/// exits.
+///
+/// An empty program ignores the signal, and `-` resets it to the default.
+/// Without arguments the traps are shown as the `trap` commands which would
+/// set them again.
+pub struct Trap;
+
+impl Builtin for Trap {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let mut args: Vec<_> = argv[1..].iter().map(|a| a.to_string_lossy()).collect();
+        if args.first().is_some_and(|a| a == "--") {
+            args.remove(0);
+        }
+
+        if args.is_empty() {
+            for (condition, action) in runtime.traps.iter() {
+                let action = action.replace('\'', "'\\''");
+                println!("trap -- '{}' {}", action, condition);
+            }
+            return Ok(ExitStatus::SUCCESS);
+        }
+
+        // A lone condition, or a list starting with a number, is reset.
+        let action = if args.len() == 1 || args[0].parse::<u32>().is_ok() {
+            None
+        } else {
+            match args.remove(0) {
+                action if action == "-" => None,
+                action => Some(action.into_owned()),
+            }
+        };
+
+        let mut status = ExitStatus::SUCCESS;
+        for arg in args {
+            let condition = match arg.parse() {
+                Ok(condition) => condition,
+                Err(()) => {
+                    eprintln!("oursh: trap: {}: invalid signal specification", arg);
+                    status = ExitStatus::Exited(1);
+                    continue;
+                }
+            };
+            let result = match (condition, &action) {
+                (Condition::Exit, _) => Ok(()),
+                (Condition::Signal(signal), None) => signal::default(signal),
+                (Condition::Signal(signal), Some(action)) if action.is_empty() => {
+                    signal::ignore(signal)
+                }
+                (Condition::Signal(signal), Some(_)) => signal::catch(signal),
+            };
+            if let Err(e) = result {
+                eprintln!("oursh: trap: {}: {}", arg, e.desc());
+                status = ExitStatus::Exited(1);
+                continue;
+            }
+            match action {
+                Some(ref action) => runtime.traps.insert(condition, action.clone()),
+                None => runtime.traps.remove(&condition),
+            };
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{
+    process::ExitStatus,
+    program::posix::builtin::Builtin,
+    program::{Result, Runtime},
+};
+use std::ffi::CString;
+
+/// Unalias builtin, removing aliases, or all of them with `-a`.
+pub struct Unalias;
+
+impl Builtin for Unalias {
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        match argv.get(1).map(|a| a.to_bytes()) {
+            None => {
+                eprintln!("oursh: unalias: usage: unalias [-a] name [name ...]");
+                Ok(ExitStatus::Exited(2))
+            }
+            Some(b"-a") => {
+                runtime.aliases.clear();
+                Ok(ExitStatus::SUCCESS)
+            }
+            Some(_) => {
+                let mut status = ExitStatus::SUCCESS;
+                for name in argv[1..].iter() {
+                    let name = name.to_string_lossy();
+                    if runtime.aliases.remove(&*name).is_none() {
+                        eprintln!("oursh: unalias: {}: not found", name);
+                        status = ExitStatus::Exited(1);
+                    }
+                }
+                Ok(status)
+            }
+        }
+    }
+}
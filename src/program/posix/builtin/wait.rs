@@ -1,42 +1,127 @@
 use crate::{
-    process::Wait as WaitTrait,
+    process::{jobs::State, signal, ExitStatus},
     program::posix::builtin::Builtin,
-    program::{Error, Result, Runtime},
+    program::{Result, Runtime},
 };
-use nix::{sys::wait::WaitStatus, unistd::Pid};
-use std::ffi::CString;
+use nix::unistd::Pid;
+use std::{ffi::CString, result};
 
-/// Wait builtin, used to block for all background jobs.
+/// Wait builtin, blocking until jobs finish.
+///
+/// Jobs are given by `%` job specs or by the process ID of any of their
+/// processes, and the status is that of the last one given, or 127 when
+/// it isn't a child of the shell. Without any, every job is waited for and
+/// the status is 0. Jobs waited for are forgotten.
+///
+/// In a forked copy of the shell, like a command of a pipeline, the jobs
+/// of the shell it was copied from aren't children to wait for.
+///
+/// A signal with a trap cuts the wait short, with a status of 128 plus the
+/// signal's number.
 pub struct Wait;
 
 impl Builtin for Wait {
-    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<WaitStatus> {
-        match argv.len() {
-            0 => unreachable!(),
-            1 => {
-                let mut last = Ok(WaitStatus::Exited(Pid::this(), 0));
-                for job in runtime.jobs.borrow().iter() {
-                    last = job.1.leader().wait().map_err(|_| Error::Runtime)
+    fn run(self, argv: Vec<CString>, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let args: Vec<_> = argv[1..].iter().map(|a| a.to_string_lossy()).collect();
+
+        if args.is_empty() {
+            let ids: Vec<usize> = runtime
+                .jobs
+                .iter()
+                .filter(|job| !job.inherited())
+                .map(|job| job.id())
+                .collect();
+            if let Err(status) = settle(&ids, runtime) {
+                return Ok(status);
+            }
+            for id in ids {
+                if let State::Done(_) = runtime.jobs[id].state() {
+                    runtime.jobs.remove(id);
                 }
-                last
             }
-            n => {
-                let mut last = Ok(WaitStatus::Exited(Pid::this(), 0));
-                for i in 2..=n {
-                    let pid: i32 = argv[i - 1].to_string_lossy().parse().unwrap();
-                    if let Some((_id, pg)) = runtime
-                        .jobs
-                        .borrow()
-                        .iter()
-                        .find(|(_, pg)| pid == pg.leader().pid().as_raw())
-                    {
-                        last = pg.leader().wait().map_err(|_| Error::Runtime)
-                    } else {
-                        eprintln!("oursh: wait: pid {} is not a child of this shell", pid);
+            runtime.jobs.clear_statuses();
+            return Ok(ExitStatus::SUCCESS);
+        }
+
+        let mut status = ExitStatus::SUCCESS;
+        for arg in args {
+            let found = if let Some(spec) = arg.strip_prefix('%') {
+                match runtime.jobs.find(spec) {
+                    Ok(id) if runtime.jobs[id].inherited() => {
+                        eprintln!("oursh: wait: {}: not a child of this shell", arg);
+                        Ok(ExitStatus::Exited(127))
+                    }
+                    Ok(id) => wait(id, None, runtime),
+                    Err(e) => {
+                        eprintln!("oursh: wait: {}: {}", arg, e);
+                        Ok(ExitStatus::Exited(127))
                     }
                 }
-                last
-            }
+            } else if let Ok(pid) = arg.parse() {
+                let pid = Pid::from_raw(pid);
+                match runtime.jobs.containing(pid) {
+                    Some(id) if !runtime.jobs[id].inherited() => wait(id, Some(pid), runtime),
+                    _ => Ok(runtime.jobs.status(pid).unwrap_or_else(|| {
+                        eprintln!("oursh: wait: pid {} is not a child of this shell", pid);
+                        ExitStatus::Exited(127)
+                    })),
+                }
+            } else {
+                eprintln!("oursh: wait: {}: not a pid or valid job spec", arg);
+                Ok(ExitStatus::Exited(1))
+            };
+            status = match found {
+                Ok(status) => status,
+                Err(interrupted) => return Ok(interrupted),
+            };
+        }
+        Ok(status)
+    }
+}
+
+// Wait for the job, returning its status, or the status of its process
+// `pid` when given. The job is forgotten once it's done.
+fn wait(
+    id: usize,
+    pid: Option<Pid>,
+    runtime: &mut Runtime,
+) -> result::Result<ExitStatus, ExitStatus> {
+    settle(&[id], runtime)?;
+    let job = &runtime.jobs[id];
+    let state = match pid {
+        Some(pid) => job
+            .processes()
+            .find(|(process, _)| process.pid() == pid)
+            .map_or(State::Running, |(_, state)| state),
+        None => job.state(),
+    };
+    if let State::Done(_) = job.state() {
+        runtime.jobs.remove(id);
+    }
+    Ok(match state {
+        State::Done(status) => status,
+        State::Stopped(signal) => ExitStatus::Stopped(signal),
+        State::Running => ExitStatus::SUCCESS,
+    })
+}
+
+// Block until none of the jobs are running any more, or until a signal
+// arrives which interrupts the wait, giving its status.
+fn settle(ids: &[usize], runtime: &mut Runtime) -> result::Result<(), ExitStatus> {
+    runtime.jobs.poll();
+    loop {
+        if ids
+            .iter()
+            .all(|id| runtime.jobs[*id].state() != State::Running)
+        {
+            return Ok(());
+        }
+        if let Some(signal) = signal::interrupted() {
+            return Err(ExitStatus::Exited(128 + signal as i32));
+        }
+        signal::suspend();
+        if signal::children() {
+            runtime.jobs.poll();
         }
     }
 }
@@ -9,7 +9,10 @@
 //! }
 //! ```
 
-use std::str::{self, CharIndices};
+use std::{
+    error, fmt,
+    str::{self, CharIndices},
+};
 
 /// A result type wrapping a token with start and end locations.
 pub type Span<T, E> = Result<(usize, T, usize), E>;
@@ -18,8 +21,25 @@ pub type Span<T, E> = Result<(usize, T, usize), E>;
 #[derive(Debug)]
 pub enum Error {
     UnrecognizedChar(usize, char, usize),
+    /// The input ended inside a quoted word, which started with the quote.
+    UnterminatedQuote(usize, char, usize),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnrecognizedChar(s, c, e) => {
+                write!(f, "unexpected character {} found at {}-{}", c, s, e)
+            }
+            Error::UnterminatedQuote(s, c, _) => {
+                write!(f, "unterminated quote {} found at {}", c, s)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
 /// Every token in the language, these are the terminals of the grammar.
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub enum Token<'input> {
@@ -49,6 +69,8 @@ pub enum Token<'input> {
     DLessDash,
     LessAnd,
     LessGreat,
+    LessParen,
+    GreatParen,
     And,
     Or,
     If,
@@ -64,7 +86,11 @@ pub enum Token<'input> {
     While,
     Until,
     For,
+    /// The `time` reserved word, which is `true` when followed by `-p`.
+    Time(bool),
     Word(&'input str),
+    /// A `name=value` word, assigning the (unquoted) value.
+    Assignment(&'input str, &'input str),
     IoNumber(usize),
     HashLang(&'input str),
     Shebang(&'input str),
@@ -89,6 +115,16 @@ pub struct Lexer<'input> {
     //
     // TODO: Generalize over this abstraction and the one needed for HERE docs.
     in_shebang: bool,
+
+    /// Could the next word name a command?
+    command: bool,
+
+    /// Is the next word the very first of a command? Reserved words are
+    /// only recognized here, elsewhere they're ordinary words.
+    first: bool,
+
+    /// Is the next word the target of a redirect?
+    redirect: bool,
 }
 
 impl<'input> Lexer<'input> {
@@ -103,14 +139,41 @@ impl<'input> Lexer<'input> {
             lookahead,
             #[cfg(feature = "shebang-block")]
             in_shebang: false,
+            command: true,
+            first: true,
+            redirect: false,
         }
     }
+
+    /// Is the next word in command position, where it names a command
+    /// (unless it's a reserved word)?
+    pub fn command_position(&self) -> bool {
+        self.command && !self.redirect
+    }
+
+    /// Carry on from where `other` left off, as if its input and this
+    /// lexer's were one, the way an alias' value is part of the program.
+    pub fn continue_from(&mut self, other: &Lexer) {
+        self.command = other.command;
+        self.first = other.first;
+        self.redirect = other.redirect;
+    }
 }
 
 impl<'input> Iterator for Lexer<'input> {
     type Item = Span<Token<'input>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let tok = self.token();
+        if let Some(Ok((_, ref token, _))) = tok {
+            self.follow(token);
+        }
+        tok
+    }
+}
+
+impl<'input> Lexer<'input> {
+    fn token(&mut self) -> Option<Span<Token<'input>, Error>> {
         #[cfg(feature = "shebang-block")]
         {
             // If we're inside a shebang, parse a full TEXT block.
@@ -138,13 +201,12 @@ impl<'input> Iterator for Lexer<'input> {
                             _ => self.advance(),
                         };
                     }
-                    self.next()
+                    self.token()
                 }
                 ')' => Some(Ok((s, Token::RParen, e))),
                 '(' => Some(Ok((s, Token::LParen, e))),
                 '`' => Some(Ok((s, Token::Backtick, e))),
                 '!' => Some(Ok((s, Token::Bang, e))),
-                '=' => Some(Ok((s, Token::Equals, e))),
                 '\\' => Some(Ok((s, Token::Backslash, e))),
                 '\'' => Some(self.single_quote(s, e)),
                 '"' => Some(self.double_quote(s, e)),
@@ -161,6 +223,11 @@ impl<'input> Iterator for Lexer<'input> {
                         self.advance();
                         Some(Ok((s, Token::Clobber, e)))
                     }
+                    #[cfg(feature = "process-substitution")]
+                    Some((_, '(', e)) => {
+                        self.advance();
+                        Some(Ok((s, Token::GreatParen, e)))
+                    }
                     _ => Some(Ok((s, Token::Great, e))),
                 },
                 '<' => match self.lookahead {
@@ -181,6 +248,11 @@ impl<'input> Iterator for Lexer<'input> {
                         self.advance();
                         Some(Ok((s, Token::LessGreat, e)))
                     }
+                    #[cfg(feature = "process-substitution")]
+                    Some((_, '(', e)) => {
+                        self.advance();
+                        Some(Ok((s, Token::LessParen, e)))
+                    }
                     _ => Some(Ok((s, Token::Less, e))),
                 },
                 '&' => {
@@ -201,9 +273,14 @@ impl<'input> Iterator for Lexer<'input> {
                 }
                 '$' => match self.lookahead {
                     Some((_, '{', e)) | Some((_, '(', e)) => Some(Ok((s, Token::Dollar, e))),
+                    // `$!` is a word of its own, though `!` can't be part of one.
+                    Some((_, '!', e)) => {
+                        self.advance();
+                        Some(Ok((s, Token::Word(&self.input[s..e]), e)))
+                    }
                     _ => Some(self.word(s, e)),
                 },
-                '{' => Some(self.block(s, s + e)),
+                '{' => Some(self.block(s, e)),
                 '}' => Some(Ok((s, Token::RBrace, e))),
                 c if is_word_start(c) => Some(self.word(s, e)),
                 c if c.is_whitespace() => continue,
@@ -218,9 +295,59 @@ impl<'input> Iterator for Lexer<'input> {
         debug!("emit<end>: {:?}", tok);
         tok
     }
-}
 
-impl<'input> Lexer<'input> {
+    // Keep track of where the next word is, after the given token.
+    fn follow(&mut self, token: &Token) {
+        match token {
+            // A command may follow these.
+            Token::Linefeed
+            | Token::Semi
+            | Token::Amper
+            | Token::And
+            | Token::Or
+            | Token::Pipe
+            | Token::Bang
+            | Token::LBrace
+            | Token::LParen
+            | Token::LessParen
+            | Token::GreatParen
+            | Token::Backtick
+            | Token::If
+            | Token::Then
+            | Token::Else
+            | Token::Elif
+            | Token::Do
+            | Token::While
+            | Token::Until
+            | Token::Time(_) => {
+                self.command = true;
+                self.first = true;
+            }
+            // The target of a redirect comes next, then whatever would have.
+            Token::Great
+            | Token::DGreat
+            | Token::GreatAnd
+            | Token::Clobber
+            | Token::Less
+            | Token::DLess
+            | Token::DLessDash
+            | Token::LessAnd
+            | Token::LessGreat
+            | Token::IoNumber(_) => {
+                self.redirect = true;
+                self.first = false;
+            }
+            Token::Word(_) | Token::Assignment(..) if self.redirect => self.redirect = false,
+            // Assignments come before a command's name.
+            Token::Assignment(..) => self.first = false,
+            Token::Dollar => {}
+            _ => {
+                self.command = false;
+                self.first = false;
+            }
+        }
+    }
+
     fn advance(&mut self) -> Option<(usize, char, usize)> {
         match self.lookahead {
             Some((s, c, e)) => {
@@ -265,9 +392,11 @@ impl<'input> Lexer<'input> {
         start: usize,
         end: usize,
     ) -> Result<(usize, Token<'input>, usize), Error> {
-        // TODO: This quietly stops at EOF.
         let (_, end) = self.take_while(start, end, |c| c != '\'');
-        self.advance(); // Consume the ending single quote.
+        // Consume the ending single quote, there may well be more to come.
+        if self.advance().is_none() {
+            return Err(Error::UnterminatedQuote(start, '\'', end));
+        }
         Ok((start, Token::Word(&self.input[start + 1..end]), end))
     }
 
@@ -278,14 +407,24 @@ impl<'input> Lexer<'input> {
         start: usize,
         end: usize,
     ) -> Result<(usize, Token<'input>, usize), Error> {
-        // TODO: This quietly stops at EOF.
         let (input, end) = self.take_while(start, end, |c| c != '"');
-        self.advance(); // Consume the ending double quote.
+        // Consume the ending double quote, there may well be more to come.
+        if self.advance().is_none() {
+            return Err(Error::UnterminatedQuote(start, '"', end));
+        }
         Ok((start, Token::Word(&input[1..]), end))
     }
 
     fn word(&mut self, start: usize, end: usize) -> Result<(usize, Token<'input>, usize), Error> {
+        let (name, end) = self.take_while(start, end, |c| c != '=' && is_word_continue(c));
+        if let (true, Some((_, '=', _))) = (is_name(name), self.lookahead) {
+            return self.assignment(start, name);
+        }
+        // Otherwise any `=` is simply part of the word.
         let (word, end) = self.take_while(start, end, is_word_continue);
+        if !self.first {
+            return Ok((start, self.io_number(word), end));
+        }
         let tok = match word {
             "if" => Token::If,
             "then" => Token::Then,
@@ -300,11 +439,53 @@ impl<'input> Lexer<'input> {
             "while" => Token::While,
             "until" => Token::Until,
             "for" => Token::For,
+            "time" => {
+                // `time -p` asks for the POSIX format, the option is part of
+                // the reserved word.
+                let rest = &self.input[end..];
+                let option = rest.trim_start_matches([' ', '\t']);
+                let posix = option
+                    .strip_prefix("-p")
+                    .is_some_and(|after| after.starts_with(char::is_whitespace));
+                if !posix {
+                    return Ok((start, Token::Time(false), end));
+                }
+                let skip = rest.len() - option.len() + 2;
+                for _ in 0..skip {
+                    self.advance();
+                }
+                return Ok((start, Token::Time(true), end + skip));
+            }
             word => self.io_number(word),
         };
         Ok((start, tok, end))
     }
 
+    fn assignment(
+        &mut self,
+        start: usize,
+        name: &'input str,
+    ) -> Result<(usize, Token<'input>, usize), Error> {
+        let (_, _, end) = self.advance().expect("lookahead `=`");
+        let (value, end) = match self.lookahead {
+            Some((_, '\'', _)) | Some((_, '"', _)) => {
+                let (s, c, e) = self.advance().unwrap();
+                let quoted = if c == '\'' {
+                    self.single_quote(s, e)?
+                } else {
+                    self.double_quote(s, e)?
+                };
+                match quoted {
+                    (_, Token::Word(value), end) => (value, end),
+                    _ => unreachable!(),
+                }
+            }
+            Some((_, c, _)) if is_word_start(c) => self.take_while(end, end, is_word_continue),
+            _ => ("", end),
+        };
+        Ok((start, Token::Assignment(name, value), end))
+    }
+
     fn io_number<'a>(&mut self, word: &'a str) -> Token<'a> {
         if let Some((_, c, _)) = self.lookahead {
             if c == '<' || c == '>' {
@@ -366,12 +547,21 @@ fn is_word_continue(ch: char) -> bool {
     match ch {
         // List of syntax from above.
         // TODO: Make this list generated.
-        ';' | ')' | '(' | '`' | '!' | '=' | '\\' | '\'' | '"' | '>' | '<' | '&' | '|' | '{'
-        | '}' | '*' => false,
+        ';' | ')' | '(' | '`' | '!' | '\\' | '\'' | '"' | '>' | '<' | '&' | '|' | '{' | '}'
+        | '*' => false,
         _ => !ch.is_whitespace(),
     }
 }
 
+// Can this word be assigned to, as in `name=value`?
+fn is_name(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -388,6 +578,20 @@ mod tests {
         assert_matches!(lexer.next(), Some(Err(Error::UnrecognizedChar(_, '*', _))));
     }
 
+    #[test]
+    fn unterminated_quote() {
+        let mut lexer = Lexer::new("echo 'a\nb");
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("echo"), _))));
+        assert_matches!(
+            lexer.next(),
+            Some(Err(Error::UnterminatedQuote(5, '\'', _)))
+        );
+        let mut lexer = Lexer::new("\"a b\"");
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("a b"), _))));
+        let mut lexer = Lexer::new("\"a b");
+        assert_matches!(lexer.next(), Some(Err(Error::UnterminatedQuote(0, '"', _))));
+    }
+
     #[test]
     fn linefeed() {
         let mut lexer = Lexer::new("\n");
@@ -406,6 +610,20 @@ mod tests {
         assert_matches!(lexer.next(), Some(Ok((_, Token::Word("$PATH"), _))));
     }
 
+    #[test]
+    fn assignments() {
+        let mut lexer = Lexer::new("a=1 b= c='x y' 1=2 =");
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Assignment("a", "1"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Assignment("b", ""), _))));
+        assert_matches!(
+            lexer.next(),
+            Some(Ok((_, Token::Assignment("c", "x y"), _)))
+        );
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("1=2"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("="), _))));
+        assert!(lexer.next().is_none());
+    }
+
     #[test]
     fn redirects() {
         let mut lexer = Lexer::new(">");
@@ -429,6 +647,18 @@ mod tests {
         assert_matches!(lexer.next(), Some(Ok((_, Token::LessGreat, _))));
     }
 
+    #[test]
+    #[cfg(feature = "process-substitution")]
+    fn process_substitution() {
+        let mut lexer = Lexer::new("<(ls) >(wc)");
+        assert_matches!(lexer.next(), Some(Ok((_, Token::LessParen, _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("ls"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::RParen, _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::GreatParen, _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("wc"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::RParen, _))));
+    }
+
     #[test]
     fn io_number() {
         let mut lexer = Lexer::new("ls -la 1> /dev/null");
@@ -459,10 +689,26 @@ mod tests {
 
     #[test]
     fn keywords() {
-        let mut lexer = Lexer::new("if ls done");
+        let mut lexer = Lexer::new("if ls done; fi");
         assert_matches!(lexer.next(), Some(Ok((_, Token::If, _))));
         assert_matches!(lexer.next(), Some(Ok((_, Token::Word("ls"), _))));
-        assert_matches!(lexer.next(), Some(Ok((_, Token::Done, _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("done"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Semi, _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Fi, _))));
+
+        let mut lexer = Lexer::new("A=1 > if then");
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Assignment("A", "1"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Great, _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("if"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("then"), _))));
+
+        let mut lexer = Lexer::new("time -p ls -p; time -l");
+        assert_matches!(lexer.next(), Some(Ok((0, Token::Time(true), 7))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("ls"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("-p"), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Semi, _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Time(false), _))));
+        assert_matches!(lexer.next(), Some(Ok((_, Token::Word("-l"), _))));
     }
 
     #[test]
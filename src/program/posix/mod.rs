@@ -114,29 +114,41 @@
 //!
 //! [1]: http://pubs.opengroup.org/onlinepubs/9699919799/
 
-use self::ast::{Assignment, Redirect};
+use self::ast::{Assignment, Redirect, Span, Word};
 use crate::{
-    process::{Process, ProcessGroup, Wait},
-    program::{Error, Result, Runtime},
+    process::{
+        io::internal,
+        session,
+        usage::{minutes, seconds, Usage},
+        ExitStatus, Process, ProcessGroup, IO,
+    },
+    program::{
+        run_traps, update_jobs, Aliases, Construct, Error, Open, Result, Run, Runtime, SyntaxError,
+    },
 };
 use dirs::home_dir;
 use lalrpop_util::ParseError;
-use nix::{sys::wait::WaitStatus, unistd::Pid};
+use nix::{
+    errno::Errno,
+    sys::signal::Signal,
+    unistd::{close, pipe},
+};
 use std::{
     env::{set_var, var},
     ffi::CString,
     fs::File,
-    io::{BufRead, Write},
-    os::unix::io::IntoRawFd,
-    process::{self, Stdio},
+    io::{self, BufRead},
+    os::unix::io::{IntoRawFd, RawFd},
+    result,
+    time::Instant,
 };
 #[cfg(feature = "raw")]
 use uuid::Uuid;
 
 #[cfg(feature = "shebang-block")]
 use {
-    self::ast::Interpreter, std::fs, std::io, std::os::unix::fs::PermissionsExt,
-    std::process::ExitStatus,
+    self::ast::Interpreter, std::fs, std::io::Write, std::os::unix::fs::PermissionsExt,
+    std::process,
 };
 
 // Re-exports.
@@ -158,229 +170,184 @@ impl super::Program for Program {
 
     fn parse<R: BufRead>(mut reader: R) -> Result<Self> {
         let mut string = String::new();
-        if reader.read_to_string(&mut string).is_err() {
-            return Err(Error::Read);
-        }
+        reader.read_to_string(&mut string).map_err(Error::Read)?;
+        Program::parse_aliased(&string, &Aliases::new())
+    }
+
+    fn commands(&self) -> &[Self::Command] {
+        &self.0[..]
+    }
+}
 
-        let lexer = lex::Lexer::new(&string);
+impl Program {
+    /// Parse a whole program, substituting the aliases as it's lexed.
+    ///
+    /// ```
+    /// use oursh::program::{posix::ast::Program, Aliases};
+    ///
+    /// let mut aliases = Aliases::new();
+    /// aliases.insert("ll".into(), "ls -l".into());
+    /// assert!(Program::parse_aliased("ll | wc", &aliases).is_ok());
+    /// ```
+    pub fn parse_aliased(string: &str, aliases: &Aliases) -> Result<Self> {
+        let lexer = alias::Aliased::new(string, aliases);
         let parser = parse::ProgramParser::new();
-        match parser.parse(&string, lexer) {
-            Ok(parsed) => Ok(parsed),
-            Err(e) => {
-                match e {
-                    ParseError::InvalidToken { location } => {
-                        eprintln!("invalid token found at {}", location);
-                    }
-                    ParseError::UnrecognizedToken { token, expected } => {
-                        let (s, t, e) = token;
-                        eprintln!(
-                            "unexpected token {:?} found at {}-{}, expecting one of: {}",
-                            t,
-                            s,
-                            e,
-                            expected.join(", ")
-                        );
-                    }
-                    ParseError::UnrecognizedEof { location, expected } => {
-                        if location == 0 {
-                            return Ok(Program(vec![]));
-                        } else {
-                            eprintln!(
-                                "unexpected EOF found at {}, expecting one of: {}",
-                                location,
-                                expected.join(", ")
-                            );
-                        }
-                    }
-                    ParseError::ExtraToken { token: (i, t, _) } => {
-                        eprintln!("extra token {:?} found at {}", t, i);
-                    }
-                    ParseError::User { error } => {
-                        let lex::Error::UnrecognizedChar(s, c, e) = error;
-                        eprintln!("unexpected character {} found at {}-{}", c, s, e);
-                    }
+        let mut recovered = vec![];
+        let result = parser.parse(string, &mut recovered, lexer);
+
+        // Report everything the parser recovered from along with the error
+        // it gave up on, if any. The parser only recovers at the end of a
+        // command, so errors inside a construct left open by an error only
+        // follow from it, and are left out.
+        let mut errors = vec![];
+        let mut settled = 0;
+        let program = {
+            let mut all: Vec<_> = recovered.into_iter().map(|r| r.error).collect();
+            let program = result.unwrap_or_else(|e| {
+                all.push(e);
+                Program(vec![])
+            });
+            for error in all {
+                let location = location(&error);
+                if location >= settled {
+                    settled = closed(string, location);
+                    errors.push(error);
                 }
-                Err(Error::Parse)
             }
+            program
+        };
+        // Nothing but whitespace is an empty program, not an error.
+        errors.retain(|e| !matches!(e, ParseError::UnrecognizedEof { location: 0, .. }));
+        // Running out of input is only a matter of waiting for more when
+        // something is still open.
+        let incomplete = match errors.as_slice() {
+            [ParseError::UnrecognizedEof { .. }]
+            | [ParseError::User {
+                error: lex::Error::UnterminatedQuote(..),
+            }] => open(string),
+            _ => None,
+        };
+        if errors.is_empty() {
+            Ok(program)
+        } else if let Some(open) = incomplete {
+            Err(Error::Incomplete(open))
+        } else {
+            let errors = errors.into_iter().map(|e| syntax_error(e, string));
+            Err(Error::Parse(errors.collect()))
         }
     }
-
-    fn commands(&self) -> &[Self::Command] {
-        &self.0[..]
-    }
 }
 
 // The semantics of a single POSIX command.
 impl super::Command for Command {}
 
 impl super::Run for Command {
-    fn run(&self, runtime: &mut Runtime) -> Result<WaitStatus> {
+    fn run(&self, runtime: &mut Runtime) -> Result<ExitStatus> {
+        let result = match self.eval(runtime) {
+            // A command which fails is reported, and the commands after it
+            // carry on with its status.
+            Err(e @ Error::Runtime { .. }) => {
+                (runtime.report)(&e);
+                *runtime.status = e.status();
+                Ok(e.status())
+            }
+            result => result,
+        };
+        // Once a command is done it's safe to run traps, and check up on
+        // the jobs.
+        run_traps(runtime);
+        update_jobs(runtime);
+        result
+    }
+}
+
+impl Command {
+    fn eval(&self, runtime: &mut Runtime) -> Result<ExitStatus> {
         #[allow(unreachable_patterns)]
         match *self {
-            Command::Simple(ref assignments, ref words, ref redirects) => {
+            Command::Simple(ref assignments, ref words, ref redirects, _) => {
                 for Assignment(name, value) in assignments {
-                    set_var(name, expand_vars(value));
+                    set_var(name, expand_vars(value, runtime));
                 }
 
-                for r in redirects {
-                    match r {
-                        Redirect::RW { n, filename, .. } => {
-                            let file = File::options()
-                                .create(true)
-                                .read(true)
-                                .write(true)
-                                .open(filename)
-                                .unwrap();
-                            let fd = file.into_raw_fd();
-                            runtime.io.0[*n as usize] = fd;
-                        }
-                        Redirect::Read { n, filename, .. } => {
-                            let file = File::options()
-                                .read(true)
-                                .write(false)
-                                .open(filename)
-                                .unwrap();
-                            let fd = file.into_raw_fd();
-                            runtime.io.0[*n as usize] = fd;
-                        }
-                        Redirect::Write {
-                            n,
-                            filename,
-                            append,
-                            ..
-                        } => {
-                            // TODO: Clobber
-                            let file = File::options()
-                                .create(true)
-                                .read(false)
-                                .write(true)
-                                .append(*append)
-                                .open(filename)
-                                .unwrap();
-                            let fd = file.into_raw_fd();
-                            runtime.io.0[*n as usize] = fd;
-                        }
-                    };
-                }
+                // Redirections only apply to this command, so restore the
+                // shell's descriptors once it's done.
+                let io = runtime.io.clone();
+                let mut opened = vec![];
+                let result = prepare(words, redirects, runtime, &mut opened)
+                    .and_then(|argv| execute(argv, self, runtime));
 
-                // expand order: variables then home
-                // $ FOO=~
-                // $ echo $FOO
-                // /home/nixpulvis
-                let argv: Vec<CString> = words
-                    .iter()
-                    .map(|word| {
-                        CString::new(&expand_home(&expand_vars(&word.0)) as &str)
-                            .expect("error in word UTF-8")
-                    })
-                    .collect();
-
-                if let Some(command) = argv.clone().first() {
-                    match command.to_string_lossy().as_ref() {
-                        "." => builtin::Dot.run(argv, runtime),
-                        ":" => builtin::Return(0).run(argv, runtime),
-                        "cd" => builtin::Cd.run(argv, runtime),
-                        "command" => builtin::Command.run(argv, runtime),
-                        "exit" => builtin::Exit.run(argv, runtime),
-                        "export" => builtin::Export.run(argv, runtime),
-                        "false" => builtin::Return(1).run(argv, runtime),
-                        "jobs" => builtin::Jobs.run(argv, runtime),
-                        "true" => builtin::Return(0).run(argv, runtime),
-                        "wait" => builtin::Wait.run(argv, runtime),
-                        _ => {
-                            let id = (runtime.jobs.borrow().len() + 1).to_string();
-                            let name = argv[0].to_string_lossy().to_string();
-                            let process =
-                                Process::fork(argv, runtime.io).map_err(|_| Error::Runtime)?;
-                            if runtime.background {
-                                let status = process.status();
-                                eprintln!("[{}]\t{}", id, process.pid());
-                                runtime.jobs.borrow_mut().push((id, ProcessGroup(process)));
-                                status.map_err(|_| Error::Runtime)
-                            } else {
-                                let status = process.wait().map_err(|_| Error::Runtime);
-                                if let Ok(WaitStatus::Exited(_, 127)) = status {
-                                    eprintln!("oursh: {}: command not found", name);
-                                }
-                                status
-                            }
-                        }
-                    }
-                } else {
-                    Ok(WaitStatus::Exited(Pid::this(), 0))
+                runtime.io = io;
+                for fd in opened {
+                    close(fd).ok();
                 }
+                if let Ok(status) = result {
+                    *runtime.status = status;
+                }
+                result
             }
-            // { sleep 3; date; }&
-            // { sleep 3; date; }& ls
-            Command::Compound(ref commands) => {
-                // TODO: Need a way to run a set of commands as one in the background. Kinda like a
-                // subshell. For now we just run them both as background as needed.
-                let mut last = WaitStatus::Exited(Pid::this(), 0);
+            Command::Compound(ref commands, _) => {
+                let mut last = ExitStatus::SUCCESS;
                 for command in commands.iter() {
                     last = command.run(runtime)?;
+                    *runtime.status = last;
                 }
                 Ok(last)
             }
-            Command::Not(ref command) => match command.run(runtime) {
-                Ok(WaitStatus::Exited(p, c)) => Ok(WaitStatus::Exited(p, (c == 0) as i32)),
-                Ok(s) => Ok(s),
-                Err(_) => Err(Error::Runtime),
-            },
-            Command::And(ref left, ref right) => match left.run(runtime) {
-                Ok(WaitStatus::Exited(_, c)) if c == 0 => {
-                    right.run(runtime).map_err(|_| Error::Runtime)
+            Command::Not(ref command, _) => {
+                let status = command.run(runtime)?;
+                Ok(ExitStatus::Exited(status.success() as i32))
+            }
+            Command::Time(ref command, posix, _) => {
+                let started = Instant::now();
+                let before = Usage::shell() + Usage::children();
+                let result = command.run(runtime);
+                let real = started.elapsed();
+                let used = Usage::shell() + Usage::children() - before;
+                if posix {
+                    eprintln!(
+                        "real {}\nuser {}\nsys {}",
+                        seconds(real),
+                        seconds(used.user),
+                        seconds(used.system)
+                    );
+                } else {
+                    eprintln!(
+                        "\nreal\t{}\nuser\t{}\nsys\t{}",
+                        minutes(real),
+                        minutes(used.user),
+                        minutes(used.system)
+                    );
                 }
-                Ok(s) => Ok(s),
-                Err(_) => Err(Error::Runtime),
+                result
+            }
+            Command::And(ref left, ref right, _) => match left.run(runtime)? {
+                status if status.success() => right.run(runtime),
+                status => Ok(status),
             },
-            Command::Or(ref left, ref right) => match left.run(runtime) {
-                Ok(WaitStatus::Exited(_, c)) if c != 0 => {
-                    right.run(runtime).map_err(|_| Error::Runtime)
-                }
-                Ok(s) => Ok(s),
-                Err(_) => Err(Error::Runtime),
+            Command::Or(ref left, ref right, _) => match left.run(runtime)? {
+                status if !status.success() => right.run(runtime),
+                status => Ok(status),
             },
-            Command::Subshell(ref program) => {
+            Command::Subshell(ref program, _) => {
                 // TODO #4: Run in a *subshell* ffs.
                 program.run(runtime)
             }
-            Command::Pipeline(ref left, ref right) => {
-                // TODO: This is obviously a temporary hack.
-                if let box Command::Simple(_assigns, lwords, _redirs) = left {
-                    let child = process::Command::new(&lwords[0].0)
-                        .args(lwords.iter().skip(1).map(|w| &w.0))
-                        .stdout(Stdio::piped())
-                        .spawn()
-                        .expect("error swawning pipeline process");
-
-                    let output = child.wait_with_output().expect("error reading stdout");
-
-                    if let box Command::Simple(_assigns, rwords, _redirs) = right {
-                        let mut child = process::Command::new(&rwords[0].0)
-                            .args(rwords.iter().skip(1).map(|w| &w.0))
-                            .stdin(Stdio::piped())
-                            .spawn()
-                            .expect("error swawning pipeline process");
-
-                        {
-                            let stdin = child.stdin.as_mut().expect("error opening stdin");
-                            stdin
-                                .write_all(&output.stdout)
-                                .expect("error writing to stdin");
-                        }
-
-                        child.wait().expect("error waiting for piped command");
-                    }
-                }
-                Ok(WaitStatus::Exited(Pid::this(), 0))
-            }
-            Command::Background(ref command) => {
+            Command::Pipeline(..) => pipeline(self, runtime),
+            // Simple commands and pipelines are already jobs of their own.
+            Command::Background(ref command, _)
+                if matches!(**command, Command::Simple(..) | Command::Pipeline(..)) =>
+            {
+                let background = runtime.background;
                 runtime.background = true;
-                command.run(runtime)
+                let result = command.run(runtime);
+                runtime.background = background;
+                result
             }
+            Command::Background(ref command, _) => background(command, runtime),
             #[cfg(feature = "shebang-block")]
-            Command::Lang(ref interpreter, ref text) => {
-                fn bridge(interpreter: &str, text: &str) -> io::Result<ExitStatus> {
+            Command::Lang(ref interpreter, ref text, _) => {
+                fn bridge(interpreter: &str, text: &str) -> io::Result<process::ExitStatus> {
                     // TODO: Even for the Shebang interpretor, we shouldn't
                     // create files like this.
                     let bridgefile = format!("/tmp/.oursh_bridge-{}", Uuid::new_v4());
@@ -415,25 +382,618 @@ impl super::Run for Command {
                         "node" => "/usr/bin/env node",
                         "python" => "/usr/bin/env python",
                         "racket" => "/usr/bin/env racket",
-                        _ => return Err(Error::Read),
+                        language => {
+                            return Err(Error::Runtime {
+                                command: language.into(),
+                                status: ExitStatus::Exited(1),
+                                source: Some(io::Error::new(
+                                    io::ErrorKind::NotFound,
+                                    "unknown language",
+                                )),
+                            })
+                        }
                     },
                     Interpreter::Shebang(ref interpreter) => interpreter,
                 };
 
-                bridge(interpreter, text).map_err(|_| Error::Read)?;
-                Ok(WaitStatus::Exited(Pid::this(), 0))
+                bridge(interpreter, text)
+                    .map(ExitStatus::from)
+                    .map_err(|e| Error::Runtime {
+                        command: interpreter.into(),
+                        status: ExitStatus::Exited(1),
+                        source: Some(e),
+                    })
             }
             #[cfg(not(feature = "shebang-block"))]
-            Command::Lang(_, _) => {
+            Command::Lang(..) => {
                 unimplemented!();
             }
         }
     }
 }
+
+/// Find the innermost construct left open at the end of the text, if any.
+fn open(text: &str) -> Option<Open> {
+    let mut opened = vec![];
+    // A trailing operator, which may be followed by newlines.
+    let mut trailing = None;
+    for token in lex::Lexer::new(text) {
+        let (s, token, e) = match token {
+            Ok(token) => token,
+            Err(lex::Error::UnterminatedQuote(s, c, _)) => {
+                return Some(Open {
+                    construct: Construct::Quote(c),
+                    span: Span::new(s, s + 1),
+                })
+            }
+            Err(_) => return None,
+        };
+        let open = |construct| Open {
+            construct,
+            span: Span::new(s, e),
+        };
+        let closes = |opened: &mut Vec<Open>, constructs: &[Construct]| {
+            if opened
+                .last()
+                .is_some_and(|o| constructs.contains(&o.construct))
+            {
+                opened.pop();
+            }
+        };
+        match token {
+            lex::Token::Linefeed => continue,
+            lex::Token::Pipe => trailing = Some(open(Construct::Pipe)),
+            lex::Token::And => trailing = Some(open(Construct::And)),
+            lex::Token::Or => trailing = Some(open(Construct::Or)),
+            token => {
+                trailing = None;
+                match token {
+                    lex::Token::If => opened.push(open(Construct::If)),
+                    lex::Token::Fi => closes(&mut opened, &[Construct::If]),
+                    lex::Token::LBrace => opened.push(open(Construct::Brace)),
+                    lex::Token::HashLang(_) | lex::Token::Shebang(_) => {
+                        opened.push(open(Construct::Lang))
+                    }
+                    lex::Token::RBrace => closes(&mut opened, &[Construct::Brace, Construct::Lang]),
+                    lex::Token::LParen | lex::Token::LessParen | lex::Token::GreatParen => {
+                        opened.push(open(Construct::Subshell))
+                    }
+                    lex::Token::RParen => closes(&mut opened, &[Construct::Subshell]),
+                    _ => {}
+                }
+            }
+        }
+    }
+    trailing.or_else(|| opened.pop())
+}
+
+/// Where the parse error happened.
+fn location(error: &ParseError<usize, lex::Token, lex::Error>) -> usize {
+    match error {
+        ParseError::InvalidToken { location } | ParseError::UnrecognizedEof { location, .. } => {
+            *location
+        }
+        ParseError::UnrecognizedToken { token: (s, ..), .. }
+        | ParseError::ExtraToken { token: (s, ..) } => *s,
+        ParseError::User {
+            error: lex::Error::UnrecognizedChar(s, ..) | lex::Error::UnterminatedQuote(s, ..),
+        } => *s,
+    }
+}
+
+/// Find where every construct open at `location`, counting one opened
+/// there, has been closed again, or `usize::MAX` if they never are.
+fn closed(text: &str, location: usize) -> usize {
+    let mut opened = vec![];
+    for token in lex::Lexer::new(text) {
+        let (s, token, e) = match token {
+            Ok(token) => token,
+            Err(_) => break,
+        };
+        let closes = |opened: &mut Vec<Construct>, constructs: &[Construct]| {
+            if opened.last().is_some_and(|o| constructs.contains(o)) {
+                opened.pop();
+                opened.is_empty() && s > location
+            } else {
+                false
+            }
+        };
+        let done = match token {
+            lex::Token::If => {
+                opened.push(Construct::If);
+                false
+            }
+            lex::Token::LBrace => {
+                opened.push(Construct::Brace);
+                false
+            }
+            lex::Token::HashLang(_) | lex::Token::Shebang(_) => {
+                opened.push(Construct::Lang);
+                false
+            }
+            lex::Token::LParen | lex::Token::LessParen | lex::Token::GreatParen => {
+                opened.push(Construct::Subshell);
+                false
+            }
+            lex::Token::Fi => closes(&mut opened, &[Construct::If]),
+            lex::Token::RBrace => closes(&mut opened, &[Construct::Brace, Construct::Lang]),
+            lex::Token::RParen => closes(&mut opened, &[Construct::Subshell]),
+            _ => false,
+        };
+        if done {
+            return e;
+        }
+        if s >= location && opened.is_empty() {
+            return location;
+        }
+    }
+    if opened.is_empty() {
+        location
+    } else {
+        usize::MAX
+    }
+}
+
+/// Give the tokens of a parse error as their text, since they borrow it.
+fn syntax_error(error: ParseError<usize, lex::Token, lex::Error>, text: &str) -> SyntaxError {
+    match error {
+        ParseError::InvalidToken { location } => ParseError::InvalidToken { location },
+        ParseError::UnrecognizedToken {
+            token: (s, _, e),
+            expected,
+        } => ParseError::UnrecognizedToken {
+            token: (s, text[s..e].into(), e),
+            expected,
+        },
+        ParseError::UnrecognizedEof { location, expected } => {
+            ParseError::UnrecognizedEof { location, expected }
+        }
+        ParseError::ExtraToken { token: (s, _, e) } => ParseError::ExtraToken {
+            token: (s, text[s..e].into(), e),
+        },
+        ParseError::User { error } => ParseError::User { error },
+    }
+}
+
+/// Expand the words of a simple command and set up its redirections.
+///
+/// Any descriptors the shell opens along the way are pushed onto `opened`,
+/// even when it fails and the command shouldn't be run.
+fn prepare(
+    words: &[Word],
+    redirects: &[Redirect],
+    runtime: &mut Runtime,
+    opened: &mut Vec<RawFd>,
+) -> Result<Vec<CString>> {
+    // expand order: variables then home
+    // $ FOO=~
+    // $ echo $FOO
+    // /home/nixpulvis
+    let mut argv: Vec<CString> = vec![];
+    for word in words {
+        let arg = match word {
+            Word::Literal(word, _) => expand_home(&expand_vars(word, runtime)),
+            Word::ReadProcess(..) | Word::WriteProcess(..) if runtime.options.posix => {
+                return Err(Error::Runtime {
+                    command: "process substitution".into(),
+                    status: ExitStatus::Exited(1),
+                    source: Some(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "not allowed with --posix",
+                    )),
+                });
+            }
+            Word::ReadProcess(program, _) | Word::WriteProcess(program, _) => {
+                let read = matches!(word, Word::ReadProcess(..));
+                match substitute(program, read, runtime, opened) {
+                    Ok(fd) => {
+                        opened.push(fd);
+                        format!("/dev/fd/{}", fd)
+                    }
+                    Err(e) => return Err(Error::failed("process substitution", e)),
+                }
+            }
+        };
+        argv.push(CString::new(arg).expect("error in word UTF-8"));
+    }
+    for fd in opened.iter() {
+        runtime.io.set(*fd, *fd);
+    }
+
+    for r in redirects {
+        match redirect(&mut runtime.io, r) {
+            Ok(fd) => opened.extend(fd),
+            Err(e) => return Err(Error::failed(r.filename(), e)),
+        }
+    }
+
+    Ok(argv)
+}
+
+/// Run an expanded simple command, either as a builtin or a new process.
+///
+/// A command which can't be found is handed to the handle, see [`handle`].
+fn execute(argv: Vec<CString>, command: &Command, runtime: &mut Runtime) -> Result<ExitStatus> {
+    if argv.is_empty() {
+        return Ok(ExitStatus::Exited(0));
+    }
+
+    let argv = match builtin(argv, runtime) {
+        Ok(result) => return result,
+        Err(argv) => handle(argv),
+    };
+    let process = Process::fork(argv, &runtime.io, None, !runtime.background)
+        .map_err(|e| Error::failed("fork", e))?;
+    // The child reports a command it couldn't run itself.
+    start(command.to_string(), ProcessGroup::new(process), runtime)
+}
+
+/// Run the command if it's a builtin, otherwise hand its arguments back.
+fn builtin(
+    argv: Vec<CString>,
+    runtime: &mut Runtime,
+) -> result::Result<Result<ExitStatus>, Vec<CString>> {
+    let name = argv[0].to_string_lossy().to_string();
+    Ok(match name.as_ref() {
+        "." => builtin::Dot.run(argv, runtime),
+        ":" => builtin::Return(0).run(argv, runtime),
+        "alias" => builtin::Alias.run(argv, runtime),
+        "bg" => builtin::Bg.run(argv, runtime),
+        "cd" => builtin::Cd.run(argv, runtime),
+        "command" => builtin::Command.run(argv, runtime),
+        "disown" => builtin::Disown.run(argv, runtime),
+        "exec" => builtin::Exec.run(argv, runtime),
+        "exit" => builtin::Exit.run(argv, runtime),
+        "export" => builtin::Export.run(argv, runtime),
+        "false" => builtin::Return(1).run(argv, runtime),
+        "fg" => builtin::Fg.run(argv, runtime),
+        "jobs" => builtin::Jobs.run(argv, runtime),
+        "kill" => builtin::Kill.run(argv, runtime),
+        "set" => builtin::Set.run(argv, runtime),
+        "times" => builtin::Times.run(argv, runtime),
+        "trap" => builtin::Trap.run(argv, runtime),
+        "true" => builtin::Return(0).run(argv, runtime),
+        "unalias" => builtin::Unalias.run(argv, runtime),
+        "wait" => builtin::Wait.run(argv, runtime),
+        _ => return Err(argv),
+    })
+}
+
+// The command run in place of commands which can't be found.
+const NOT_FOUND_HANDLE: &str = "command_not_found_handle";
+
+/// The command to run for one which isn't a builtin.
+///
+/// When the command can't be found, and there's a `command_not_found_handle`
+/// in `$PATH`, the handle is run instead, with the missing command and its
+/// arguments. As the shell has no functions, the handle can only be a file
+/// in `$PATH`, though it's looked for by the shell, before anything forks.
+fn handle(argv: Vec<CString>) -> Vec<CString> {
+    let name = argv[0].as_bytes();
+    if name.contains(&b'/') || Process::new(vec![argv[0].clone()]).found() {
+        return argv;
+    }
+    let mut handle = vec![CString::new(NOT_FOUND_HANDLE).unwrap()];
+    if !Process::new(handle.clone()).found() {
+        return argv;
+    }
+    handle.extend(argv);
+    handle
+}
+
+/// Run each command of a pipeline in a process of its own, all as one job,
+/// with the output of each command piped to the input of the next.
+fn pipeline(command: &Command, runtime: &mut Runtime) -> Result<ExitStatus> {
+    let commands = command.pipeline();
+    let foreground = !runtime.background;
+    let mut group: Option<ProcessGroup> = None;
+    // The read end of the last pipe, the next command's input.
+    let mut input = None;
+    // Why the rest of the pipeline couldn't be started, if it couldn't.
+    let mut failed = None;
+    for (i, command) in commands.iter().enumerate() {
+        let (read, write) = if i + 1 < commands.len() {
+            match pipe().and_then(|(r, w)| Ok((internal(r)?, internal(w)?))) {
+                Ok((r, w)) => (Some(r), Some(w)),
+                Err(e) => {
+                    failed = Some(Error::failed("pipe", e));
+                    break;
+                }
+            }
+        } else {
+            (None, None)
+        };
+        let mut io = runtime.io.clone();
+        if let Some(fd) = input {
+            io.set(0, fd);
+        }
+        if let Some(fd) = write {
+            io.set(1, fd);
+        }
+
+        let pgid = group.as_ref().map(ProcessGroup::pgid);
+        let forked = Process::fork_job(describe(command), pgid, foreground, || {
+            if let Some(fd) = read {
+                close(fd).ok();
+            }
+            runtime.jobs.forked();
+            runtime.io = io;
+            piped(command, runtime)
+        });
+        // The pipe's ends belong to the commands on either side now.
+        for fd in input.into_iter().chain(write) {
+            close(fd).ok();
+        }
+        input = read;
+        match (forked, &mut group) {
+            (Ok(process), Some(group)) => group.push(process),
+            (Ok(process), None) => group = Some(ProcessGroup::new(process)),
+            (Err(e), _) => {
+                failed = Some(Error::failed("fork", e));
+                break;
+            }
+        }
+    }
+    if let Some(fd) = input {
+        close(fd).ok();
+    }
+
+    // The commands which did start are still a job, seen through as usual.
+    let status = match group {
+        Some(group) => start(command.to_string(), group, runtime)?,
+        None => ExitStatus::Exited(1),
+    };
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(status),
+    }
+}
+
+/// Run a command in a forked copy of the shell, as a single job in the
+/// background, like `{ sleep 3; date; } &`.
+fn background(command: &Command, runtime: &mut Runtime) -> Result<ExitStatus> {
+    let text = command.to_string();
+    let argv = vec![CString::new(text.as_str()).expect("error in command UTF-8")];
+    let forked = Process::fork_job(argv, None, false, || {
+        runtime.jobs.forked();
+        runtime.background = false;
+        match command.run(runtime) {
+            Ok(status) => status.code(),
+            Err(e) => {
+                (runtime.report)(&e);
+                e.status().code()
+            }
+        }
+    });
+    match forked {
+        Ok(process) => {
+            let background = runtime.background;
+            runtime.background = true;
+            let result = start(text, ProcessGroup::new(process), runtime);
+            runtime.background = background;
+            result
+        }
+        Err(e) => Err(Error::failed("fork", e)),
+    }
+}
+
+// The words of a simple command as they're written, to show for its job.
+fn describe(command: &Command) -> Vec<CString> {
+    let words = match command {
+        Command::Simple(_, words, _, _) => words.as_slice(),
+        _ => &[],
+    };
+    words
+        .iter()
+        .map(|word| match word {
+            Word::Literal(word, _) => word.as_str(),
+            Word::ReadProcess(..) => "<(...)",
+            Word::WriteProcess(..) => ">(...)",
+        })
+        .map(|word| CString::new(word).expect("error in word UTF-8"))
+        .collect()
+}
+
+// Run a command of a pipeline, in the process forked for it. Commands which
+// aren't builtins replace the process, rather than forking yet again.
+fn piped(command: &Command, runtime: &mut Runtime) -> i32 {
+    let argv = match command {
+        Command::Simple(assignments, words, redirects, _) => {
+            for Assignment(name, value) in assignments {
+                set_var(name, expand_vars(value, runtime));
+            }
+            let mut opened = vec![];
+            match prepare(words, redirects, runtime, &mut opened) {
+                Ok(argv) => Some(argv),
+                Err(e) => {
+                    (runtime.report)(&e);
+                    return e.status().code();
+                }
+            }
+        }
+        _ => None,
+    };
+    if runtime.io.dup().is_err() {
+        return 1;
+    }
+    runtime.io = IO::default();
+
+    let result = match argv {
+        Some(argv) if argv.is_empty() => return 0,
+        Some(argv) => match builtin(argv, runtime) {
+            Ok(result) => result,
+            Err(argv) => return Process::new(handle(argv)).exec_with(&runtime.io),
+        },
+        None => command.run(runtime),
+    };
+    match result {
+        Ok(status) => status.code(),
+        Err(e) => {
+            (runtime.report)(&e);
+            e.status().code()
+        }
+    }
+}
+
+/// Add a job that was just started, running `text`, and wait on it unless
+/// it's meant to run in the background.
+fn start(text: String, group: ProcessGroup, runtime: &mut Runtime) -> Result<ExitStatus> {
+    let pgid = group.pgid();
+    let id = runtime.jobs.add(text, group);
+    if runtime.background {
+        runtime.jobs.backgrounded(id);
+        // Like `sh`, only an interactive shell says which job it started.
+        if runtime.options.interactive {
+            eprintln!("[{}]\t{}", id, pgid);
+        }
+        return Ok(ExitStatus::SUCCESS);
+    }
+    foreground(id, runtime)
+}
+
+/// Wait on the job `id` in the foreground.
+///
+/// A job which stops is kept as the current job, to be continued later,
+/// any other job is done with.
+fn foreground(id: usize, runtime: &mut Runtime) -> Result<ExitStatus> {
+    let result = runtime.jobs[id].wait();
+    session::reclaim();
+    match result {
+        Ok(status @ ExitStatus::Stopped(_)) => {
+            runtime.jobs.promote(id);
+            eprintln!("\n{}", runtime.jobs.report(id));
+            Ok(status)
+        }
+        Ok(status) => {
+            runtime.jobs.remove(id);
+            // Whatever the user typed to interrupt the job is left on the
+            // line.
+            if let ExitStatus::Signaled(Signal::SIGINT, _) = status {
+                println!();
+            }
+            Ok(status)
+        }
+        Err(e) => {
+            let job = runtime.jobs.remove(id).expect("the job waited on");
+            Err(Error::failed(job.text(), e))
+        }
+    }
+}
+
+/// Apply a single redirection to `io`, returning the descriptor opened for
+/// it (if any), which the caller is responsible for closing.
+fn redirect(io: &mut IO, redirect: &Redirect) -> nix::Result<Option<RawFd>> {
+    let (n, filename, options) = match redirect {
+        Redirect::Read {
+            n,
+            filename,
+            duplicate: true,
+            ..
+        }
+        | Redirect::Write {
+            n,
+            filename,
+            duplicate: true,
+            ..
+        } => {
+            // [n]<&word and [n]>&word, where word is a descriptor or `-`.
+            if filename == "-" {
+                io.close(*n);
+            } else {
+                match filename.parse::<RawFd>().ok().and_then(|m| io.get(m)) {
+                    Some(fd) => io.set(*n, fd),
+                    None => return Err(Errno::EBADF),
+                }
+            }
+            return Ok(None);
+        }
+        Redirect::RW { n, filename, .. } => {
+            let mut options = File::options();
+            options.create(true).read(true).write(true).truncate(false);
+            (n, filename, options)
+        }
+        Redirect::Read { n, filename, .. } => {
+            let mut options = File::options();
+            options.read(true);
+            (n, filename, options)
+        }
+        Redirect::Write {
+            n,
+            filename,
+            append,
+            ..
+        } => {
+            // TODO: Clobber
+            let mut options = File::options();
+            options
+                .create(true)
+                .write(true)
+                .append(*append)
+                .truncate(!*append);
+            (n, filename, options)
+        }
+    };
+
+    let file = options
+        .open(filename)
+        .map_err(|e| Errno::from_i32(e.raw_os_error().unwrap_or_default()))?;
+    let fd = internal(file.into_raw_fd())?;
+    io.set(*n, fd);
+    Ok(Some(fd))
+}
+
+/// Start the program of a process substitution, returning the command's
+/// end of the pipe between them.
+///
+/// The program reads from the pipe when `read` is false, and writes to it
+/// otherwise, running in the background as a helper process.
+fn substitute(
+    program: &Program,
+    read: bool,
+    runtime: &mut Runtime,
+    opened: &[RawFd],
+) -> nix::Result<RawFd> {
+    let (r, w) = pipe()?;
+    let (r, w) = (internal(r)?, internal(w)?);
+    let (ours, theirs, n, argv) = if read {
+        (r, w, 1, "<(...)")
+    } else {
+        (w, r, 0, ">(...)")
+    };
+
+    let helper = Process::fork_shell(vec![CString::new(argv).unwrap()], || {
+        // Nothing else the command is about to be given belongs to us.
+        for fd in opened.iter().chain(Some(&ours)) {
+            close(*fd).ok();
+        }
+        runtime.jobs.forked();
+        runtime.io.set(n, theirs);
+        if runtime.io.dup().is_err() {
+            return 1;
+        }
+        runtime.io = IO::default();
+        match program.run(runtime) {
+            Ok(status) => status.code(),
+            _ => 1,
+        }
+    });
+    close(theirs)?;
+    match helper {
+        Ok(helper) => {
+            runtime.jobs.add_helper(helper);
+            Ok(ours)
+        }
+        Err(e) => {
+            close(ours)?;
+            Err(e)
+        }
+    }
+}
+
 fn expand_home(word: &str) -> String {
     if let Some(w) = word.strip_prefix('~') {
         if let Some(path) = home_dir() {
-            format!("{}{}", &path.to_str().expect("error: home not set"), w)
+            format!("{}{}", path.to_str().expect("error: home not set"), w)
         } else {
             "~".into()
         }
@@ -445,7 +1005,7 @@ fn expand_home(word: &str) -> String {
 // "$" => "$"
 // "$ " => "$ "
 // "$USER" => "nixpulvis"
-fn expand_vars(string: &str) -> String {
+fn expand_vars(string: &str, runtime: &Runtime) -> String {
     let mut result = String::new();
     let mut variable = String::new();
     let mut variable_start = -1;
@@ -455,7 +1015,7 @@ fn expand_vars(string: &str) -> String {
             if variable.is_empty() {
                 result.push(c);
             } else {
-                result += &var(&variable).unwrap_or_else(|_| "".into());
+                result += &lookup(&variable, runtime);
             }
             variable.clear();
             variable_start = -1;
@@ -466,7 +1026,7 @@ fn expand_vars(string: &str) -> String {
         } else if c == ' ' {
             variable_start = -1;
         } else if c == '@' || c == ':' {
-            result += &var(&variable).unwrap_or_else(|_| "".into());
+            result += &lookup(&variable, runtime);
             variable.clear();
             variable_start = -1;
             result.push(c);
@@ -479,13 +1039,30 @@ fn expand_vars(string: &str) -> String {
             result.push(c);
         }
     }
-    result += &var(&variable).unwrap_or_else(|_| "".into());
+    result += &lookup(&variable, runtime);
     result
 }
 
+/// The value of a variable, special parameters like `$?` come from the
+/// shell itself, everything else from the environment.
+fn lookup(name: &str, runtime: &Runtime) -> String {
+    match name {
+        "?" => runtime.status.code().to_string(),
+        "!" => runtime
+            .jobs
+            .last_background()
+            .map(|pid| pid.to_string())
+            .unwrap_or_default(),
+        name => var(name).unwrap_or_default(),
+    }
+}
+
 // Builtin functions for the POSIX language, like `exit` and `cd`.
 pub mod builtin;
 
+// Substituting aliases for the names of commands.
+pub mod alias;
+
 // The POSIX AST data structures and helper functions.
 pub mod ast;
 
@@ -516,4 +1093,50 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.unwrap().0.is_empty());
     }
+
+    #[test]
+    fn program_parse_error() {
+        let result: Result<Program> = Program::parse(b"echo )" as &[u8]);
+        assert_matches!(&result, Err(Error::Parse(errors)) if errors.len() == 1);
+        assert_matches!(&result, Err(Error::Parse(errors)) if matches!(
+            &errors[0],
+            ParseError::UnrecognizedToken { token: (5, t, 6), .. } if t == ")"
+        ));
+        assert_eq!(ExitStatus::Exited(2), result.unwrap_err().status());
+    }
+
+    #[test]
+    fn program_parse_errors() {
+        let text = b"echo )\ntrue\necho a && ;\nls; | wc" as &[u8];
+        let result: Result<Program> = Program::parse(text);
+        assert_matches!(&result, Err(Error::Parse(errors)) if errors.len() == 3);
+    }
+
+    #[test]
+    fn program_parse_errors_in_construct() {
+        // The rest of the `if` only follows from the first error.
+        let text = b"echo a | if true; then cat; fi" as &[u8];
+        let result: Result<Program> = Program::parse(text);
+        assert_matches!(&result, Err(Error::Parse(errors)) if errors.len() == 1);
+        let text = b"if ; then echo; fi\necho a | { cat; }; fi" as &[u8];
+        let result: Result<Program> = Program::parse(text);
+        assert_matches!(&result, Err(Error::Parse(errors)) if errors.len() == 3);
+    }
+
+    #[test]
+    fn program_parse_incomplete() {
+        let open = |text: &str| match Program::parse(text.as_bytes()) {
+            Err(Error::Incomplete(open)) => Some(open.construct),
+            _ => None,
+        };
+        assert_eq!(Some(Construct::Quote('"')), open("echo \"a"));
+        assert_eq!(Some(Construct::If), open("if true; then\n echo 1\n"));
+        assert_eq!(Some(Construct::Pipe), open("{ echo 1 |\n"));
+        assert_eq!(Some(Construct::Brace), open("{ echo 1 |\n wc;"));
+        assert_eq!(Some(Construct::Or), open("false ||"));
+        assert_eq!(Some(Construct::Lang), open("{#!ruby; puts 1"));
+        assert_eq!(None, open("echo ) |"));
+        assert_eq!(None, open("echo >"));
+        assert!(Program::parse(b"true &&\n\necho 1" as &[u8]).is_ok());
+    }
 }
@@ -1,7 +1,31 @@
-use crate::process::{Jobs, IO};
 #[cfg(feature = "history")]
 use crate::repl::history::History;
+use crate::{
+    process::{signal::Condition, ExitStatus, Jobs, IO},
+    program::Error,
+};
 use docopt::ArgvMap;
+use std::collections::BTreeMap;
+
+/// The shell's aliases, from each name to the text it stands for.
+pub type Aliases = BTreeMap<String, String>;
+
+/// The shell's traps, from each condition to the program run on it.
+pub type Traps = BTreeMap<Condition, String>;
+
+/// The shell's options, given on the command line or set with the `set`
+/// builtin.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// The shell reads its commands from a user, `-i`.
+    pub interactive: bool,
+    /// Only the POSIX language is accepted, without extensions like process
+    /// substitution, `--posix`.
+    pub posix: bool,
+    /// Report jobs as soon as they stop or finish, rather than before the
+    /// next prompt, `set -b`.
+    pub notify: bool,
+}
 
 #[derive(Debug)]
 pub struct Runtime<'a> {
@@ -9,6 +33,14 @@ pub struct Runtime<'a> {
     pub io: IO,
     pub jobs: &'a mut Jobs,
     pub args: &'a ArgvMap,
+    /// The status of the last command run, `$?`.
+    pub status: &'a mut ExitStatus,
+    pub aliases: &'a mut Aliases,
+    pub traps: &'a mut Traps,
+    pub options: &'a mut Options,
+    /// Report a command which failed, see [`Error::Runtime`]. The commands
+    /// after it carry on, with the failure's status as `$?`.
+    pub report: fn(&Error),
     #[cfg(feature = "history")]
     pub history: &'a mut History,
 }
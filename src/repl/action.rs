@@ -1,71 +1,76 @@
 //! Actions to be bound to input methods.
 use std::io::{Stdout, Write};
 
-use crate::process::{Jobs, IO};
-use crate::program::{parse_and_run, Runtime};
+use crate::process::signal;
+use crate::program::{
+    hangup, may_exit, parse_and_run, parse_primary, run_exit_trap, run_traps, Error, Runtime,
+};
 use crate::repl::prompt;
-use docopt::ArgvMap;
 use std::process::exit;
 use termion::{cursor::DetectCursorPos, raw::RawTerminal};
 
-#[cfg(feature = "history")]
-use super::history::History;
-
 #[cfg(feature = "completion")]
 use super::completion::*;
 
 pub struct Action;
 
-pub struct ActionContext<'a> {
+pub struct ActionContext<'a, 'b> {
     pub stdout: &'a mut RawTerminal<Stdout>,
-    pub io: &'a mut IO,
-    pub jobs: &'a mut Jobs,
-    pub args: &'a mut ArgvMap,
+    /// The state of the shell, which each program entered is run with.
+    pub runtime: &'a mut Runtime<'b>,
+    /// Where the first prompt, and the prompt of each further line, end.
     // TODO: Remove this field.
     #[cfg(feature = "raw")]
-    pub prompt_length: u16,
+    pub prompt_lengths: (u16, u16),
+    /// The program being entered, with a newline between each of its lines.
     #[cfg(feature = "raw")]
     pub text: &'a mut String,
-    #[cfg(feature = "history")]
-    pub history: &'a mut History,
+    /// The line of the program being edited.
+    #[cfg(feature = "raw")]
+    pub line: usize,
 }
 
 #[cfg(feature = "raw")]
 impl Action {
     pub fn enter(context: &mut ActionContext) {
-        // Perform a raw mode line break.
+        // Perform a raw mode line break, after the whole program.
+        to_end(context);
         print!("\n\r");
         context.stdout.flush().unwrap();
 
+        // Keep reading lines until the program is complete.
+        if let Err(Error::Incomplete(_)) = parse_primary(context.text.as_bytes()) {
+            context.text.push('\n');
+            context.line += 1;
+            ps2(context);
+            return;
+        }
+
         // Run the command.
         context.stdout.suspend_raw_mode().unwrap();
-        let mut runtime = Runtime {
-            background: false,
-            io: context.io.clone(),
-            jobs: context.jobs,
-            args: context.args,
-            #[cfg(feature = "history")]
-            history: context.history,
-        };
-        if parse_and_run(context.text, &mut runtime).is_ok() {
-            #[cfg(feature = "history")]
-            context.history.add(&context.text, 1);
-        }
+        // Running the program adds the whole thing to the history.
+        parse_and_run("stdin", context.text, context.runtime).ok();
         context.stdout.activate_raw_mode().unwrap();
 
         // Reset for the next program.
         context.text.clear();
+        context.line = 0;
         #[cfg(feature = "history")]
-        context.history.reset_index();
+        context.runtime.history.reset_index();
 
-        prompt::ps1(&mut context.stdout);
+        ps1(context);
     }
 
     pub fn insert(context: &mut ActionContext, c: char) {
         if let Ok((x, y)) = context.stdout.cursor_pos() {
-            let i = (x - context.prompt_length) as usize;
+            let i = line_start(context.text, context.line) + (x - prompt_length(context)) as usize;
             context.text.insert(i, c);
-            print!("{}{}", &context.text[i..], termion::cursor::Goto(x + 1, y));
+            let end = line_end(context.text, context.line);
+            print!(
+                "{}{}",
+                &context.text[i..end],
+                termion::cursor::Goto(x + 1, y)
+            );
         } else {
             context.text.push(c);
             print!("{}", c);
@@ -75,26 +80,79 @@ impl Action {
 
     pub fn backspace(context: &mut ActionContext) {
         if let Ok((x, y)) = context.stdout.cursor_pos() {
-            if x > context.prompt_length {
-                let i = x - context.prompt_length;
-                context.text.remove((i - 1) as usize);
+            let start = line_start(context.text, context.line);
+            let prompt_length = prompt_length(context);
+            if x > prompt_length {
+                let i = start + (x - prompt_length) as usize;
+                context.text.remove(i - 1);
+                let end = line_end(context.text, context.line);
                 print!(
                     "{}{}{}{}",
-                    termion::cursor::Goto(context.prompt_length, y),
+                    termion::cursor::Goto(prompt_length, y),
                     termion::clear::UntilNewline,
-                    context.text,
+                    &context.text[start..end],
                     termion::cursor::Goto(x - 1, y)
                 );
                 context.stdout.flush().unwrap();
+            } else if start > 0 {
+                // Join this line onto the end of the one before it.
+                let line = context.line - 1;
+                let column = start - 1 - line_start(context.text, line);
+                erase(context);
+                context.text.remove(start - 1);
+                display(context);
+                place(context, line, column);
             }
         }
     }
 
     pub fn interrupt(context: &mut ActionContext) {
         // TODO: Send signal if we're running a program.
+        to_end(context);
         context.text.clear();
+        context.line = 0;
         print!("^C\n\r");
-        prompt::ps1(&mut context.stdout);
+        ps1(context);
+    }
+
+    /// Report the jobs which stopped or finished while waiting at the
+    /// prompt, with `set -b`, then show the program being entered again.
+    pub fn notify(context: &mut ActionContext) {
+        if !signal::children() {
+            return;
+        }
+        context.runtime.jobs.poll();
+        if !context.runtime.options.notify || !context.runtime.jobs.unreported() {
+            return;
+        }
+
+        to_end(context);
+        print!("\n\r");
+        context.stdout.suspend_raw_mode().unwrap();
+        context.runtime.jobs.notify();
+        context.stdout.activate_raw_mode().unwrap();
+        display(context);
+    }
+
+    /// Run the traps of signals which arrived while waiting at the prompt,
+    /// then show the program being entered again.
+    pub fn trap(context: &mut ActionContext) {
+        if signal::interrupted().is_none() {
+            return;
+        }
+
+        to_end(context);
+        print!("\n\r");
+        // The terminal may be gone, after a hangup.
+        context.stdout.suspend_raw_mode().ok();
+        run_traps(context.runtime);
+        context.stdout.activate_raw_mode().ok();
+        display(context);
+    }
+
+    /// Hang up, once the terminal has gone away.
+    pub fn hangup(context: &mut ActionContext) -> ! {
+        hangup(context.runtime)
     }
 
     pub fn eof(context: &mut ActionContext) {
@@ -102,9 +160,17 @@ impl Action {
             print!("exit\n\r");
             context.stdout.flush().unwrap();
 
+            context.stdout.suspend_raw_mode().unwrap();
+            if !may_exit(context.runtime) {
+                context.stdout.activate_raw_mode().unwrap();
+                display(context);
+                return;
+            }
+
             // Save history to file in $HOME.
             #[cfg(feature = "history")]
-            context.history.save();
+            context.runtime.history.save_or_report();
+            run_exit_trap(context.runtime);
 
             // Manually drop the raw terminal.
             // TODO: Needed?
@@ -117,7 +183,7 @@ impl Action {
 
     pub fn left(context: &mut ActionContext) {
         if let Ok((x, _y)) = context.stdout.cursor_pos() {
-            if x > context.prompt_length {
+            if x > prompt_length(context) {
                 print!("{}", termion::cursor::Left(1));
                 context.stdout.flush().unwrap();
             }
@@ -126,23 +192,55 @@ impl Action {
 
     pub fn right(context: &mut ActionContext) {
         if let Ok((x, _y)) = context.stdout.cursor_pos() {
-            if x < context.prompt_length + context.text.len() as u16 {
+            if x < prompt_length(context) + line_len(context.text, context.line) {
                 print!("{}", termion::cursor::Right(1));
                 context.stdout.flush().unwrap();
             }
         }
     }
 
+    /// Move to the line above in the program, or back through the history
+    /// from its first line.
+    pub fn up(context: &mut ActionContext) {
+        if context.line == 0 {
+            #[cfg(feature = "history")]
+            Action::history_up(context);
+        } else if let Ok((x, y)) = context.stdout.cursor_pos() {
+            let column = (x - prompt_length(context)) as usize;
+            context.line -= 1;
+            let column = column.min(line_len(context.text, context.line) as usize);
+            let x = prompt_length(context) + column as u16;
+            print!("{}", termion::cursor::Goto(x, y - 1));
+            context.stdout.flush().unwrap();
+        }
+    }
+
+    /// Move to the line below in the program, or forward through the
+    /// history from its last line.
+    pub fn down(context: &mut ActionContext) {
+        if context.line == last_line(context.text) {
+            #[cfg(feature = "history")]
+            Action::history_down(context);
+        } else if let Ok((x, y)) = context.stdout.cursor_pos() {
+            let column = (x - prompt_length(context)) as usize;
+            context.line += 1;
+            let column = column.min(line_len(context.text, context.line) as usize);
+            let x = prompt_length(context) + column as u16;
+            print!("{}", termion::cursor::Goto(x, y + 1));
+            context.stdout.flush().unwrap();
+        }
+    }
+
     pub fn home(context: &mut ActionContext) {
         if let Ok((_x, y)) = context.stdout.cursor_pos() {
-            print!("{}", termion::cursor::Goto(context.prompt_length, y));
+            print!("{}", termion::cursor::Goto(prompt_length(context), y));
             context.stdout.flush().unwrap();
         }
     }
 
     pub fn end(context: &mut ActionContext) {
         if let Ok((_x, y)) = context.stdout.cursor_pos() {
-            let end = context.prompt_length + context.text.len() as u16;
+            let end = prompt_length(context) + line_len(context.text, context.line);
             print!("{}", termion::cursor::Goto(end, y));
             context.stdout.flush().unwrap();
         }
@@ -150,41 +248,26 @@ impl Action {
 
     pub fn clear(context: &mut ActionContext) {
         print!("{}{}", termion::clear::All, termion::cursor::Goto(1, 1));
-        prompt::ps1(&mut context.stdout);
+        display(context);
     }
 
     #[cfg(feature = "history")]
     pub fn history_up(context: &mut ActionContext) {
-        print!(
-            "{}{}",
-            termion::cursor::Left(1000), // XXX
-            termion::clear::CurrentLine
-        );
-        context.prompt.display(&mut context.stdout);
-
-        if let Some(history_text) = context.history.get_up() {
+        if let Some(history_text) = context.runtime.history.get_up() {
+            erase(context);
             *context.text = history_text;
-            print!("{}", context.text);
+            display(context);
         }
-        context.stdout.flush().unwrap();
     }
 
     #[cfg(feature = "history")]
     pub fn history_down(context: &mut ActionContext) {
-        print!(
-            "{}{}",
-            termion::cursor::Left(1000), // XXX
-            termion::clear::CurrentLine
-        );
-        context.prompt.display(&mut context.stdout);
-
-        if let Some(history_text) = context.history.get_down() {
-            *context.text = history_text;
-            print!("{}", context.text);
-            context.stdout.flush().unwrap();
-        } else {
-            context.text.clear();
+        erase(context);
+        match context.runtime.history.get_down() {
+            Some(history_text) => *context.text = history_text,
+            None => context.text.clear(),
         }
+        display(context);
     }
 
     #[cfg(feature = "completion")]
@@ -200,23 +283,121 @@ impl Action {
                 } else {
                     print!("\n\r{}\n\r", possibilities.join("\t"));
                 }
-                prompt::ps1(&mut context.stdout);
-                print!("{}", context.text);
-                context.stdout.flush().unwrap();
+                display(context);
             }
             Completion::Complete(t) => {
+                erase(context);
                 *context.text = t;
-                print!(
-                    "{}{}",
-                    termion::cursor::Left(1000), // XXX
-                    termion::clear::CurrentLine
-                );
-                context.stdout.flush().unwrap();
-                prompt::ps1(&mut context.stdout);
-                print!("{}", context.text);
-                context.stdout.flush().unwrap();
+                display(context);
             }
             Completion::None => {}
         }
     }
 }
+
+// The index of the start of the line of the text.
+fn line_start(text: &str, line: usize) -> usize {
+    match line.checked_sub(1) {
+        Some(above) => text
+            .match_indices('\n')
+            .nth(above)
+            .map_or(text.len(), |(i, _)| i + 1),
+        None => 0,
+    }
+}
+
+// The index of the end of the line of the text, before its newline.
+fn line_end(text: &str, line: usize) -> usize {
+    text.match_indices('\n')
+        .nth(line)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+// The length of the line of the text.
+fn line_len(text: &str, line: usize) -> u16 {
+    (line_end(text, line) - line_start(text, line)) as u16
+}
+
+// The number of the text's last line.
+fn last_line(text: &str) -> usize {
+    text.matches('\n').count()
+}
+
+// Where the prompt of the line being edited ends.
+fn prompt_length(context: &ActionContext) -> u16 {
+    if context.line == 0 {
+        context.prompt_lengths.0
+    } else {
+        context.prompt_lengths.1
+    }
+}
+
+// Display the first prompt, and record where it ends.
+fn ps1(context: &mut ActionContext) {
+    prompt::ps1(&mut context.stdout);
+    context.prompt_lengths.0 = prompt_end(context, context.prompt_lengths.0);
+}
+
+// Display the prompt of a further line, and record where it ends.
+fn ps2(context: &mut ActionContext) {
+    prompt::ps2(&mut context.stdout);
+    context.prompt_lengths.1 = prompt_end(context, context.prompt_lengths.1);
+}
+
+// XXX: Hack to get the prompt length, call right after displaying it.
+fn prompt_end(context: &mut ActionContext, default: u16) -> u16 {
+    context.stdout.cursor_pos().map_or(default, |(x, _)| x)
+}
+
+// Move the cursor down to the end of the program's last line.
+fn to_end(context: &mut ActionContext) {
+    let below = last_line(context.text) - context.line;
+    if below > 0 {
+        print!("{}", termion::cursor::Down(below as u16));
+    }
+    context.line = last_line(context.text);
+    Action::end(context);
+}
+
+// Move the cursor from the end of the program to the column of the line.
+fn place(context: &mut ActionContext, line: usize, column: usize) {
+    let above = last_line(context.text) - line;
+    if above > 0 {
+        print!("{}", termion::cursor::Up(above as u16));
+    }
+    context.line = line;
+    Action::home(context);
+    if column > 0 {
+        print!("{}", termion::cursor::Right(column as u16));
+    }
+    context.stdout.flush().unwrap();
+}
+
+// Clear every line of the program shown so far, leaving the cursor where
+// its first prompt was.
+fn erase(context: &mut ActionContext) {
+    if context.line > 0 {
+        print!("{}", termion::cursor::Up(context.line as u16));
+    }
+    print!(
+        "{}{}",
+        termion::cursor::Left(1000),
+        termion::clear::AfterCursor
+    ); // XXX
+}
+
+// Show the whole program, each line after its prompt, with the cursor at the
+// end.
+fn display(context: &mut ActionContext) {
+    let text = context.text.clone();
+    let mut lines = text.split('\n');
+    ps1(context);
+    print!("{}", lines.next().unwrap_or_default());
+    for line in lines {
+        print!("\n\r");
+        ps2(context);
+        print!("{}", line);
+    }
+    context.line = last_line(context.text);
+    context.stdout.flush().unwrap();
+}
@@ -1,5 +1,10 @@
 //! Keeps a record of previous commands, used for completion and archeology.
-use std::{env, fs::File, io::prelude::*, path::Path};
+use std::{
+    env,
+    fs::File,
+    io::{self, prelude::*},
+    path::Path,
+};
 
 /// The history of a user's provided commands.
 #[derive(Debug)]
@@ -58,7 +63,7 @@ impl History {
 
     pub fn get_down(&mut self) -> Option<String> {
         match self.0 {
-            Some(i) if i == 0 => self.0 = None,
+            Some(0) => self.0 = None,
             Some(i) => self.0 = Some(i.saturating_sub(1)),
             None => {}
         };
@@ -85,7 +90,7 @@ impl History {
             //         println!("{:?}", s);
             //     })
             // }).collect::<Vec<String, usize>>();
-            let hist = contents.split("\n").map(|s| (String::from(s), 0));
+            let hist = entries(&contents).into_iter().map(|s| (s, 0));
 
             // Add each entry to the history in order.
             for (text, index) in hist {
@@ -99,15 +104,77 @@ impl History {
         history
     }
 
-    pub fn save(&self) -> Result<(), ()> {
-        let home = env::var("HOME").expect("HOME variable not set.");
+    pub fn save(&self) -> io::Result<()> {
+        let home = env::var("HOME")
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "HOME variable not set"))?;
         let history_path = format!("{}/.oursh_history", home);
-        let mut f = File::create(&history_path).expect("error cannot find history");
+        let mut f = File::create(history_path)?;
         for (text, _) in self.1.iter() {
-            f.write_all(text.as_bytes()).expect("error writing history");
-            f.write_all(b"\n").expect("error writing history");
+            f.write_all(entry(text).as_bytes())?;
+            f.write_all(b"\n")?;
         }
 
         Ok(())
     }
+
+    /// Save the history like [`save`](History::save), only reporting why it
+    /// couldn't be, as the shell carries on (or exits) either way.
+    pub fn save_or_report(&self) {
+        if let Err(e) = self.save() {
+            eprintln!("oursh: history: {}", e);
+        }
+    }
+}
+
+// Write the text as a single line of the history file. Multi-line programs
+// are kept together by escaping their newlines with a `\`, so the text's own
+// backslashes are escaped too.
+fn entry(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\\n")
+}
+
+// Split the history file into its entries, undoing the escapes of `entry`.
+fn entries(contents: &str) -> Vec<String> {
+    let mut entries = vec![];
+    let mut entry = String::new();
+    let mut chars = contents.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ ('\\' | '\n')) => entry.push(c),
+                Some(c) => {
+                    entry.push('\\');
+                    entry.push(c);
+                }
+                None => entry.push('\\'),
+            },
+            '\n' => entries.push(entry.split_off(0)),
+            c => entry.push(c),
+        }
+    }
+    if !entry.is_empty() {
+        entries.push(entry);
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_round_trip() {
+        let texts = ["ls", "if true; then\necho 1\nfi", "echo \\", "echo \\\nls"];
+        let contents: String = texts.iter().map(|text| entry(text) + "\n").collect();
+        assert_eq!(texts.to_vec(), entries(&contents));
+    }
+
+    #[test]
+    fn entries_of_a_trailing_backslash() {
+        // A real trailing `\` isn't a line continued.
+        assert_eq!(
+            vec!["echo \\", "ls"],
+            entries(&(entry("echo \\") + "\nls\n"))
+        );
+    }
 }
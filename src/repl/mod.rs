@@ -3,15 +3,20 @@
 //! There will be *absolutely no* blocking STDIN/OUT/ERR on things like tab
 //! completion or other potentially slow, or user defined behavior.
 
-use crate::process::{Jobs, IO};
-use docopt::ArgvMap;
-use nix::sys::wait::WaitStatus;
-use nix::unistd::Pid;
+use crate::{process::ExitStatus, program::Runtime};
 use std::io::{Stdin, Stdout};
 
 #[cfg(feature = "raw")]
 use {
     crate::repl::action::{Action, ActionContext},
+    nix::{
+        poll::{poll, PollFd, PollFlags},
+        unistd,
+    },
+    std::{
+        io::{self, ErrorKind, Read},
+        os::unix::io::AsRawFd,
+    },
     termion::cursor::DetectCursorPos,
     termion::event::Key,
     termion::input::TermRead,
@@ -20,13 +25,10 @@ use {
 
 #[cfg(not(feature = "raw"))]
 use {
-    crate::program::{parse_and_run, Runtime},
+    crate::program::{may_exit, parse_and_run, parse_primary, run_exit_trap, run_traps, Error},
     std::io::BufRead,
 };
 
-#[cfg(feature = "history")]
-use self::history::History;
-
 /// Start a REPL over the strings the user provides.
 ///
 /// ## Examples
@@ -48,24 +50,18 @@ use self::history::History;
 pub fn start(
     mut stdin: Stdin,
     mut stdout: Stdout,
-    io: &mut IO,
-    jobs: &mut Jobs,
-    args: &mut ArgvMap,
-) -> crate::program::Result<WaitStatus> {
-    // Load history from file in $HOME.
-    #[cfg(feature = "history")]
-    let mut history = History::load();
-
+    runtime: &mut Runtime,
+) -> crate::program::Result<ExitStatus> {
     #[cfg(feature = "raw")]
-    raw_loop(stdin, stdout, io, jobs, args);
+    raw_loop(stdin, stdout, runtime);
     #[cfg(not(feature = "raw"))]
-    buffered_loop(stdin, stdout, io, jobs, args);
+    buffered_loop(stdin, stdout, runtime);
 
-    Ok(WaitStatus::Exited(Pid::this(), 0))
+    Ok(*runtime.status)
 }
 
 #[cfg(feature = "raw")]
-fn raw_loop(stdin: Stdin, stdout: Stdout, io: &mut IO, jobs: &mut Jobs, args: &mut ArgvMap) {
+fn raw_loop(stdin: Stdin, stdout: Stdout, runtime: &mut Runtime) {
     // Convert the tty's stdout into raw mode.
     let mut stdout = stdout.into_raw_mode().expect("error opening raw mode");
 
@@ -81,17 +77,23 @@ fn raw_loop(stdin: Stdin, stdout: Stdout, io: &mut IO, jobs: &mut Jobs, args: &m
     // Create an context to pass to the actions.
     let mut context = ActionContext {
         stdout: &mut stdout,
-        io: io,
-        jobs: jobs,
-        args: args,
-        prompt_length: prompt_length,
+        runtime,
+        prompt_lengths: (prompt_length, prompt_length),
         text: &mut text,
-        #[cfg(feature = "history")]
-        history: &mut history,
+        line: 0,
     };
     // Iterate the keys as a user presses them.
     // TODO #5: Mouse?
-    for c in stdin.keys() {
+    for c in Input(stdin).keys() {
+        let c = match c {
+            // A signal arrived while waiting for a key.
+            Err(e) if e.kind() == ErrorKind::Interrupted => {
+                Action::trap(&mut context);
+                Action::notify(&mut context);
+                continue;
+            }
+            c => c,
+        };
         match c.unwrap() {
             Key::Char('\n') => Action::enter(&mut context),
             #[cfg(feature = "completion")]
@@ -99,33 +101,59 @@ fn raw_loop(stdin: Stdin, stdout: Stdout, io: &mut IO, jobs: &mut Jobs, args: &m
             Key::Char(c) => Action::insert(&mut context, c),
             Key::Left => Action::left(&mut context),
             Key::Right => Action::right(&mut context),
+            Key::Up => Action::up(&mut context),
+            Key::Down => Action::down(&mut context),
             Key::Backspace => Action::backspace(&mut context),
             Key::Ctrl('a') => Action::home(&mut context),
             Key::Ctrl('e') => Action::end(&mut context),
             Key::Ctrl('c') => Action::interrupt(&mut context),
             Key::Ctrl('d') => Action::eof(&mut context),
             Key::Ctrl('l') => Action::clear(&mut context),
-            #[cfg(feature = "history")]
-            Key::Up => Action::history_up(&mut context),
-            #[cfg(feature = "history")]
-            Key::Down => Action::history_down(&mut context),
             _ => {}
         }
     }
+    // In raw mode the end of the input is only ever the terminal hanging
+    // up, ctrl-d is a key like any other.
+    Action::hangup(&mut context);
+}
+
+// The terminal's input, read without any buffering so it can be waited on
+// for each key. Signals which arrive while waiting end the wait early, as
+// an `Interrupted` error.
+#[cfg(feature = "raw")]
+struct Input(Stdin);
+
+#[cfg(feature = "raw")]
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Unlike `read`, `poll` is never restarted after a signal.
+        poll(&mut [PollFd::new(&self.0, PollFlags::POLLIN)], -1)?;
+        Ok(unistd::read(self.0.as_raw_fd(), buf)?)
+    }
 }
 
 #[cfg(not(feature = "raw"))]
-fn buffered_loop(
-    stdin: Stdin,
-    mut stdout: Stdout,
-    io: &mut IO,
-    jobs: &mut Jobs,
-    args: &mut ArgvMap,
-) {
+fn buffered_loop(stdin: Stdin, mut stdout: Stdout, runtime: &mut Runtime) {
     // Display the initial prompt.
     prompt::ps1(&mut stdout);
 
-    for line in stdin.lock().lines() {
+    // The program read so far, which may take a number of lines.
+    let mut text = String::new();
+    let mut lines = stdin.lock().lines();
+    loop {
+        let line = match lines.next() {
+            Some(line) => line,
+            // Running out of input exits the shell, like `exit`.
+            None => {
+                run_traps(runtime);
+                if may_exit(runtime) {
+                    run_exit_trap(runtime);
+                    return;
+                }
+                prompt::ps1(&mut stdout);
+                continue;
+            }
+        };
         let line = line.unwrap(); // TODO: Exit codes
                                   //     let readline = runtime.rl.as_mut().unwrap().readline(&prompt);
                                   //     match readline {
@@ -145,22 +173,18 @@ fn buffered_loop(
                                   //             code = 130;
                                   //             break;
                                   //         }
-        let mut runtime = Runtime {
-            background: false,
-            io: io.clone(),
-            jobs: jobs,
-            args: args,
-            #[cfg(feature = "history")]
-            history: history,
-        };
-        if parse_and_run(&line, &mut runtime).is_ok() {
-            #[cfg(feature = "history")]
-            history.add(&line, 1);
+        text += &line;
+        if let Err(Error::Incomplete(_)) = parse_primary(text.as_bytes()) {
+            text.push('\n');
+            prompt::ps2(&mut stdout);
+            continue;
         }
+
+        // Running the program adds the whole thing to the history.
+        parse_and_run("stdin", &text, runtime).ok();
+        text.clear();
         #[cfg(feature = "history")]
-        history.add(&line, 1);
-        #[cfg(feature = "history")]
-        history.reset_index();
+        runtime.history.reset_index();
 
         prompt::ps1(&mut stdout);
     }
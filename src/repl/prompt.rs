@@ -10,6 +10,13 @@ pub fn ps1(stdout: &mut impl Write) {
     stdout.flush().unwrap();
 }
 
+/// Display the prompt for each further line of an unfinished program.
+pub fn ps2(stdout: &mut impl Write) {
+    let prompt = expand_prompt(env::var("PS2").unwrap_or_else(|_| "> ".into()));
+    write!(stdout, "{}", prompt).unwrap();
+    stdout.flush().unwrap();
+}
+
 fn expand_prompt(prompt: String) -> String {
     let mut result = String::new();
     let mut command = false;
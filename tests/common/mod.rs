@@ -212,3 +212,40 @@ macro_rules! assert_posix {
         assert_eq!($stdout, oursh_stdout);
     }};
 }
+
+/// A directory of executable scripts for a test, removed again once it's
+/// dropped, even when an assertion fails.
+#[allow(dead_code)]
+pub struct ScriptDir(std::path::PathBuf);
+
+#[allow(dead_code)]
+impl ScriptDir {
+    /// Create a directory, `name`d for the test, which no other test
+    /// process shares.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("oursh-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).expect("error creating script directory");
+        ScriptDir(dir)
+    }
+
+    /// Write an executable script to the directory, returning its path.
+    pub fn script(&self, name: &str, text: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = self.0.join(name);
+        std::fs::write(&path, text).expect("error writing script");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .expect("error making script executable");
+        path
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl Drop for ScriptDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
@@ -1,5 +1,26 @@
 mod common;
 
+use common::ScriptDir;
+
+#[test]
+#[cfg(feature = "process-substitution")]
+fn process_substitution_command() {
+    assert_oursh!("cat <(echo 1) <(echo 2)", "1\n2\n");
+    assert_oursh!("diff <(echo 1) <(echo 1) && echo same", "same\n");
+    assert_oursh!("cp README.md >(head -n 1)", "# oursh\n");
+    // It isn't POSIX.
+    let output = shell!(
+        "target/debug/oursh",
+        &["--noprofile", "--posix"],
+        "cat <(echo 1); echo $?"
+    );
+    assert_eq!("1\n", String::from_utf8_lossy(&output.stdout));
+    assert_eq!(
+        "oursh: process substitution: not allowed with --posix\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
 #[test]
 #[cfg(feature = "shebang-block")]
 fn shebang_block_sh_command() {
@@ -60,3 +81,189 @@ fn shebang_block_rust_command() {
         "hello world!\n"
     );
 }
+
+#[test]
+#[cfg(feature = "history")]
+fn history_not_saved() {
+    // A history which can't be saved is reported, and the shell exits as
+    // usual.
+    let output = oursh!("HOME=/nonexistent/oursh\necho hi; exit\n");
+    assert!(output.status.success());
+    assert_eq!("hi\n", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("oursh: history: "));
+}
+
+#[test]
+fn jobs_command() {
+    assert_oursh!(
+        "sleep 5 & sleep 5 | cat & jobs; jobs -p %2 | wc -l; kill %1 %2; wait; jobs",
+        "[1]-\tRunning\tsleep 5 &\n[2]+\tRunning\tsleep 5 | cat &\n1\n"
+    );
+    // A command of a pipeline lists the shell's jobs, but can't wait on
+    // them, so the job is still running after.
+    let output = oursh!("sleep 5 & jobs | cat; wait %1 | cat; wait $! | cat; jobs; kill %1");
+    assert_eq!(
+        "[1]+\tRunning\tsleep 5 &\n[1]+\tRunning\tsleep 5 &\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("oursh: wait: %1: not a child of this shell\noursh: wait: pid "));
+    assert!(stderr.ends_with(" is not a child of this shell\n"));
+    // Numbering starts over once every job is done.
+    assert_oursh!(
+        "sleep 5 & kill %1; wait; sleep 5 & jobs %?5; kill %+",
+        "[1]+\tRunning\tsleep 5 &\n"
+    );
+}
+
+#[test]
+fn background_job_line() {
+    // Only an interactive shell says which job it started.
+    assert_oursh!("sleep 0 & wait", "", "");
+    let output = shell!(
+        "target/debug/oursh",
+        &["--noprofile", "-i"],
+        "sleep 0 & wait"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("[1]\t"));
+}
+
+#[test]
+fn set_command() {
+    assert_oursh!(
+        "set -o; set -b; set +o",
+        "notify         \toff\ntiming         \toff\nset -o notify\nset +o timing\n"
+    );
+    assert_oursh!(
+        "set -o notify +b -o timing; set -o",
+        "notify         \toff\ntiming         \ton\n"
+    );
+    assert_oursh!("exec 2>/dev/null; set -q; echo $?", "2\n");
+}
+
+#[test]
+fn disown_command() {
+    assert_oursh!(
+        "sleep 1 & sleep 5 & disown %1; jobs; disown -h; jobs; kill %2; disown -a; jobs",
+        "[2]+\tRunning\tsleep 5 &\n[2]+\tRunning\tsleep 5 &\n"
+    );
+    assert_oursh!("exec 2>/dev/null; disown; echo $?", "1\n");
+    assert_oursh!("exec 2>/dev/null; disown -x; echo $?", "2\n");
+}
+
+#[test]
+fn stdin_stream_syntax_error() {
+    // Piped input runs each command as it's read, so the commands before a
+    // syntax error have already run when it stops the shell.
+    let output = oursh!("echo first\necho a |;\necho last\n");
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("first\n", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("oursh: stdin:2:9: unexpected token `;`\n"));
+}
+
+#[test]
+fn command_string_syntax_error() {
+    // Like piped input, a `-c` string runs each command as it's read.
+    let output = shell!(
+        "target/debug/oursh",
+        &["--noprofile", "-c", "echo first\necho a |;\necho last"],
+        ""
+    );
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!("first\n", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("oursh: -c:2:9: unexpected token `;`\n"));
+}
+
+#[test]
+fn command_string_alias() {
+    // An alias applies to the lines after the one defining it.
+    let output = shell!(
+        "target/debug/oursh",
+        &["--noprofile", "-c", "alias ll='echo hi'\nll there"],
+        ""
+    );
+    assert!(output.status.success());
+    assert_eq!("hi there\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn alias_syntax_error() {
+    // The error in the alias' value is at its name, as written.
+    let output = oursh!("alias x='echo )'\necho ok; x\n");
+    assert_eq!(Some(2), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("oursh: stdin:2:10: unexpected token `x`\necho ok; x\n         ^"));
+}
+
+#[test]
+fn execution_errors() {
+    assert_oursh!(
+        "cat < /nonexistent; echo 1 > /nonexistent/f; . /nonexistent; cd /nonexistent; echo $?",
+        "1\n",
+        "oursh: /nonexistent: No such file or directory\n\
+         oursh: /nonexistent/f: No such file or directory\n\
+         oursh: .: /nonexistent: No such file or directory\n\
+         oursh: cd: /nonexistent: No such file or directory\n"
+    );
+}
+
+#[test]
+fn exec_command() {
+    // An interactive shell keeps going, with its descriptors put back.
+    let output = shell!(
+        "target/debug/oursh",
+        &["--noprofile", "-i"],
+        "exec nosuchcommand >/dev/null 2>&1; echo $?"
+    );
+    assert_eq!("127\n", String::from_utf8_lossy(&output.stdout));
+    assert_oursh!(!"exec nosuchcommand; echo 1");
+}
+
+#[test]
+fn command_not_found() {
+    // Reported once, by the process which couldn't run it.
+    assert_oursh!(
+        "exec 2>&1; nosuchcommand; echo $?",
+        "oursh: nosuchcommand: command not found\n127\n"
+    );
+    // Unless there's a handle for it, in `$PATH`.
+    let dir = ScriptDir::new("handle");
+    dir.script(
+        "command_not_found_handle",
+        "#!/bin/sh\necho did you mean: $*\nexit 3\n",
+    );
+    let path = format!("PATH={}:/usr/bin:/bin", dir.path().display());
+    assert_oursh!(
+        format!("{} nosuchcommand a b; echo $?", path),
+        "did you mean: nosuchcommand a b\n3\n"
+    );
+    // Even in a pipeline, or run by `exec`.
+    assert_oursh!(
+        format!("export {}; nosuchcommand a | cat; echo $?", path),
+        "did you mean: nosuchcommand a\n0\n"
+    );
+    let output = oursh!(format!("export {}; exec nosuchcommand a; echo no", path));
+    assert_eq!(Some(3), output.status.code());
+    assert_eq!(
+        "did you mean: nosuchcommand a\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn time_command() {
+    // Unlike `bash --posix`, `time -p` is the reserved word too.
+    assert_oursh!("exec 2>/dev/null; time -p false; echo $?", "1\n");
+    assert_oursh!("exec 2>/dev/null; time -p sleep 0 | cat; echo $?", "0\n");
+}
+
+#[test]
+fn times_command() {
+    assert_oursh!("sleep 0; times | wc -l", "2\n");
+    assert_oursh!(
+        "set -o timing; sleep 0; sleep 0 | cat; set +o timing; sleep 0; times | cut -sf2",
+        "sleep 0\nsleep 0 | cat\n"
+    );
+}
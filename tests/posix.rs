@@ -1,5 +1,7 @@
 mod common;
 
+use common::ScriptDir;
+
 #[test]
 fn hello_world() {
     assert_posix!("echo hello world", "hello world\n");
@@ -19,6 +21,16 @@ fn builtin_exit() {
     assert_posix!(!"exit 1");
 }
 
+#[test]
+fn builtin_exec() {
+    assert_posix!("exec echo 1; echo 2", "1\n");
+    assert_posix!("exec 3>&1; echo 1 >&3", "1\n");
+    assert_posix!("exec 2>&1; ls /nonexistent 2>/dev/null; echo 1", "1\n");
+    // A shell which isn't interactive exits when the command can't be run.
+    assert_posix!(!"exec 2>/dev/null; exec nosuchcommand; echo 1");
+    assert_posix!(!"exec 2>/dev/null; exec /etc/passwd; echo 1");
+}
+
 #[test]
 fn builtin_null() {
     assert_posix!(":");
@@ -113,9 +125,12 @@ fn single_pipeline_command() {
 }
 
 #[test]
-#[ignore]
 fn chained_pipeline_command() {
     assert_posix!("cat README.md | head | wc -l", "10\n");
+    assert_posix!("printf 'b\\na\\nc\\n' | sort | head -n 1", "a\n");
+    assert_posix!("yes | head -n 2", "y\ny\n");
+    assert_posix!("echo hi | false; echo $?", "1\n");
+    assert_posix!("true | echo ok", "ok\n");
 }
 
 #[test]
@@ -141,6 +156,26 @@ fn variable_command() {
     assert_posix!("echo \" $$ $ \"", " $$ $ \n");
 }
 
+#[test]
+fn status_variable() {
+    assert_posix!("true; echo $?", "0\n");
+    assert_posix!("false; echo $?", "1\n");
+    assert_posix!("! true; echo $?", "1\n");
+    assert_posix!("false || echo $?", "1\n");
+    assert_posix!("sh -c 'exit 3'; echo $?", "3\n");
+    assert_posix!("exec 2>/dev/null; sh -c 'kill -9 $$'; echo $?", "137\n");
+}
+
+#[test]
+fn stdin_stream() {
+    assert_posix!("echo 'a\nb'", "a\nb\n");
+    assert_posix!("echo a |\nwc -c", "2\n");
+    assert_posix!("cat\nhello\n", "hello\n");
+    assert_posix!(!"echo first\necho a |;\necho last\n");
+    // Unlike a syntax error, a command which fails doesn't stop the rest.
+    assert_posix!("exec 2>/dev/null\ncat </nonexistent\necho $?\n", "1\n");
+}
+
 #[test]
 fn background_command() {
     assert_posix!("sleep 1 & echo 1", "1\n");
@@ -148,7 +183,130 @@ fn background_command() {
     // assert_posix!("sleep 1 & echo 1", "1\n", "[1]\t(\d*)\n");
 }
 
+#[test]
+fn background_compound_command() {
+    assert_posix!(
+        "exec 2>/dev/null; { sleep 0.2; echo 2; } & echo 1; wait; echo 3",
+        "1\n2\n3\n"
+    );
+    assert_posix!(
+        "exec 2>/dev/null; { echo 1; echo 2; } & wait $!; echo $?",
+        "1\n2\n0\n"
+    );
+    assert_posix!("exec 2>/dev/null; true && echo 1 & wait; echo 2", "1\n2\n");
+}
+
+#[test]
+fn redirect_command() {
+    assert_posix!("echo 1 3>&1 >&3", "1\n");
+    assert_posix!("echo 1 3>&1 1>&2 2>&3", "");
+}
+
+#[test]
+fn redirect_error_command() {
+    assert_posix!("exec 2>/dev/null; cat < /nonexistent; echo 1", "1\n");
+    assert_posix!("exec 2>/dev/null; echo 1 > /nonexistent/f; echo 2", "2\n");
+}
+
 #[test]
 fn equal_sign() {
     assert_posix!("echo =", "=\n");
+    assert_posix!("echo a=b", "a=b\n");
+}
+
+#[test]
+fn alias_command() {
+    assert_posix!("alias say='echo said'\nsay hi", "said hi\n");
+    assert_posix!(
+        "exec 2>/dev/null; alias say=echo\n'say' hi; echo $?",
+        "127\n"
+    );
+    assert_posix!("alias a='echo ' b=hi\na b", "hi\n");
+    assert_posix!(
+        "alias b=c a=\"echo it's\"\nalias",
+        "a='echo it'\\''s'\nb='c'\n"
+    );
+    assert_posix!("alias a=b\nunalias a\nalias", "");
+}
+
+#[test]
+fn trap_command() {
+    assert_posix!("trap 'echo bye' EXIT; echo hi", "hi\nbye\n");
+    assert_posix!(
+        "trap 'echo got' USR1; printf 'kill -USR1 \\044PPID' | sh; echo after",
+        "got\nafter\n"
+    );
+    assert_posix!(
+        "trap 'echo bye' 0; trap '' INT; trap 'echo term' 15; trap - INT; trap",
+        "trap -- 'echo bye' EXIT\ntrap -- 'echo term' TERM\nbye\n"
+    );
+    assert_posix!("exec 2>/dev/null; trap 'echo x' FOO; echo $?", "1\n");
+    // The commands of a `CHLD` trap don't set it off again.
+    assert_posix!(
+        "trap 'echo child' CHLD; sleep 0; trap - CHLD; sleep 0; echo end",
+        "child\nend\n"
+    );
+}
+
+#[test]
+fn fg_bg_without_job_control() {
+    assert_posix!("exec 2>/dev/null; sleep 1 & fg; echo $?", "1\n");
+    assert_posix!("exec 2>/dev/null; sleep 1 & bg %1; echo $?", "1\n");
+}
+
+#[test]
+fn kill_command() {
+    assert_posix!("kill -l 9; kill -l 130; kill -l TERM", "KILL\nINT\n15\n");
+    assert_posix!("exec 2>/dev/null; kill %3; echo $?", "1\n");
+    assert_posix!("exec 2>/dev/null; kill -s FOO 1; echo $?", "1\n");
+    assert_posix!("exec 2>/dev/null; kill; echo $?", "2\n");
+}
+
+#[test]
+fn wait_command() {
+    assert_posix!("exec 2>/dev/null; sleep 0 & wait $!; echo $?", "0\n");
+    assert_posix!("exec 2>/dev/null; sh -c 'exit 3' & wait %1; echo $?", "3\n");
+    assert_posix!(
+        "exec 2>/dev/null; sh -c 'exit 4' & sleep 0.1; wait $!; echo $?",
+        "4\n"
+    );
+    assert_posix!("exec 2>/dev/null; wait 99999; echo $?", "127\n");
+    assert_posix!("exec 2>/dev/null; wait %2; echo $?", "127\n");
+    assert_posix!("exec 2>/dev/null; wait x; echo $?", "1\n");
+    assert_posix!("exec 2>/dev/null; sh -c 'exit 5' & wait; echo $?", "0\n");
+    assert_posix!(
+        "exec 2>/dev/null; trap 'echo usr1' USR1; sleep 5 & \
+         printf 'sleep 0.2; kill -USR1 \\044PPID' | sh & wait %1; echo $?; kill %1",
+        "usr1\n138\n"
+    );
+}
+
+#[test]
+fn time_command() {
+    assert_posix!("exec 2>/dev/null; time sleep 0 | cat; echo $?", "0\n");
+    assert_posix!("exec 2>/dev/null; time ! false; echo $?", "0\n");
+    assert_posix!("echo time -p", "time -p\n");
+}
+
+#[test]
+fn command_not_run() {
+    assert_posix!("exec 2>/dev/null; nosuchcommand; echo $?", "127\n");
+    assert_posix!("exec 2>/dev/null; /nonexistent/command; echo $?", "127\n");
+    assert_posix!("exec 2>/dev/null; /; echo $?", "126\n");
+    assert_posix!("exec 2>/dev/null; /etc/passwd; echo $?", "126\n");
+    // Text files without a `#!` line are run as scripts.
+    let dir = ScriptDir::new("enoexec");
+    let script = dir.script("script", "echo script\n");
+    assert_posix!(format!("{}; echo $?", script.display()), "script\n0\n");
+}
+
+#[test]
+fn reserved_words() {
+    assert_posix!("echo if then else fi done", "if then else fi done\n");
+    assert_posix!("if true; then echo fi; else echo else; fi", "fi\n");
+    assert_posix!(
+        "if false\nthen echo then\nelif true\nthen echo elif\nfi",
+        "elif\n"
+    );
+    assert_posix!("alias fi=echo\necho fi", "fi\n");
 }
@@ -11,6 +11,22 @@ fn hello_world() {
                     "hello world\n");
 }
 
+#[test]
+fn missing_script() {
+    let output = oursh!(> "./scripts/nonexistent.sh");
+    assert_eq!(Some(127), output.status.code());
+    assert_eq!(
+        "oursh: ./scripts/nonexistent.sh: No such file or directory\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn alias() {
+    // An alias applies to the lines after the one defining it.
+    assert_oursh!(> "./scripts/alias.sh", "hi there\n");
+}
+
 #[test]
 fn multiline() {
     assert_oursh!(> "./scripts/multiline.sh", "12\n");